@@ -0,0 +1,271 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::models::{ImageRecord, ProviderKind, UserSettings, WallpaperBackend};
+use crate::services::disk_cache::DiskCache;
+use crate::services::error::ServiceError;
+use crate::services::wallpaper::set_wallpaper;
+use crate::services::export::copy_to_new_file;
+use crate::services::naming::{file_extension, render_file_name};
+use crate::services::{fetch_waifu_image_async, fetch_waifu_images_async, runtime};
+
+/// Gives up on filling `--count` after this many requests in a row return
+/// nothing new.
+const MAX_EMPTY_ROUNDS: usize = 3;
+
+#[derive(Parser)]
+#[command(name = "waifu-generator", version, about = "Generate waifu images")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Keep settings, cache, favorites and history under this directory
+    /// instead of the XDG locations. Also set by WAIFU_GENERATOR_HOME.
+    #[arg(long, global = true, value_name = "DIR")]
+    pub home: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Download images without a display and print what was saved as JSON
+    Fetch(FetchArgs),
+    /// Set a new image as the desktop wallpaper, once or every few minutes
+    Wallpaper(WallpaperArgs),
+}
+
+/// Which images to pick, shared by every subcommand.
+#[derive(Args)]
+pub struct SourceArgs {
+    /// Category to download. Defaults to a random pick from the categories
+    /// selected in the settings.
+    #[arg(long)]
+    pub tag: Option<String>,
+
+    /// Treat --tag as an NSFW category
+    #[arg(long, requires = "tag")]
+    pub nsfw: bool,
+
+    /// Image source. Defaults to the one chosen in the settings.
+    #[arg(long, value_enum)]
+    pub provider: Option<ProviderKind>,
+}
+
+#[derive(Args)]
+pub struct FetchArgs {
+    #[command(flatten)]
+    pub source: SourceArgs,
+
+    /// Number of images to download
+    #[arg(long, default_value_t = 1)]
+    pub count: usize,
+
+    /// Directory the images are written to
+    #[arg(long, default_value = ".")]
+    pub out: PathBuf,
+
+    /// Also set the first downloaded image as the desktop wallpaper
+    #[arg(long)]
+    pub set_wallpaper: bool,
+}
+
+#[derive(Args)]
+pub struct WallpaperArgs {
+    #[command(flatten)]
+    pub source: SourceArgs,
+
+    /// Keep running and switch to a new image every MINUTES minutes
+    #[arg(long, value_name = "MINUTES", value_parser = clap::value_parser!(u64).range(1..))]
+    pub every: Option<u64>,
+
+    /// How to apply the wallpaper. Defaults to the settings, then to the
+    /// detected desktop.
+    #[arg(long, value_enum)]
+    pub backend: Option<BackendArg>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum BackendArg {
+    Gnome,
+    Kde,
+    Swww,
+    Swaybg,
+    Feh,
+}
+
+impl From<BackendArg> for WallpaperBackend {
+    fn from(backend: BackendArg) -> Self {
+        match backend {
+            BackendArg::Gnome => WallpaperBackend::Gnome,
+            BackendArg::Kde => WallpaperBackend::Kde,
+            BackendArg::Swww => WallpaperBackend::Swww,
+            BackendArg::Swaybg => WallpaperBackend::Swaybg,
+            BackendArg::Feh => WallpaperBackend::Feh,
+        }
+    }
+}
+
+/// One downloaded image, as printed on stdout.
+#[derive(Serialize)]
+struct SavedImage {
+    path: PathBuf,
+    url: String,
+    tag: String,
+    provider: ProviderKind,
+    is_nsfw: bool,
+}
+
+/// Runs a CLI subcommand and returns the process exit code.
+pub fn run(command: Command) -> i32 {
+    match command {
+        Command::Fetch(args) => match runtime::runtime().block_on(fetch(args)) {
+            Ok(saved) => match serde_json::to_string_pretty(&saved) {
+                Ok(json) => {
+                    println!("{}", json);
+                    0
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    1
+                }
+            },
+            Err(e) => report_error(&e),
+        },
+        Command::Wallpaper(args) => match runtime::runtime().block_on(rotate_wallpaper(args)) {
+            Ok(()) => 0,
+            Err(e) => report_error(&e),
+        },
+    }
+}
+
+fn report_error(e: &ServiceError) -> i32 {
+    eprintln!("Error: {}", e);
+    eprintln!("{}", e.guidance());
+    1
+}
+
+fn load_settings(source: &SourceArgs) -> UserSettings {
+    let config_path = UserSettings::default_config_path();
+    let mut settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+    if let Some(provider) = source.provider {
+        settings.provider = provider;
+    }
+    settings
+}
+
+async fn fetch(args: FetchArgs) -> Result<Vec<SavedImage>, ServiceError> {
+    let settings = load_settings(&args.source);
+
+    let images = resolve_images(&settings, &args.source, args.count).await?;
+
+    tokio::fs::create_dir_all(&args.out).await?;
+    let disk_cache = DiskCache::for_settings(&settings);
+
+    let mut saved = Vec::new();
+    for image in images {
+        match save_image(&disk_cache, &image, &args.out).await {
+            Ok(path) => {
+                eprintln!("Saved {}", path.display());
+                saved.push(SavedImage {
+                    path,
+                    url: image.url,
+                    tag: image.tag,
+                    provider: image.provider,
+                    is_nsfw: image.is_nsfw,
+                });
+            }
+            Err(e) => eprintln!("Error while saving {}: {}", image.url, e),
+        }
+    }
+
+    if saved.is_empty() && args.count > 0 {
+        return Err(ServiceError::Io("No image could be saved".to_string()));
+    }
+
+    if args.set_wallpaper
+        && let Some(first) = saved.first()
+    {
+        let path = first.path.clone();
+        runtime::spawn_blocking(move || set_wallpaper(&path, &settings)).await??;
+    }
+    Ok(saved)
+}
+
+/// Sets a new wallpaper, then keeps doing so every `args.every` minutes if
+/// given. In that mode a failed round is reported and retried at the next tick.
+async fn rotate_wallpaper(args: WallpaperArgs) -> Result<(), ServiceError> {
+    let Some(minutes) = args.every else {
+        return next_wallpaper(&args).await;
+    };
+
+    let mut interval = tokio::time::interval(Duration::from_secs(minutes * 60));
+    loop {
+        interval.tick().await;
+        if let Err(e) = next_wallpaper(&args).await {
+            eprintln!("Error while changing wallpaper: {}", e);
+        }
+    }
+}
+
+async fn next_wallpaper(args: &WallpaperArgs) -> Result<(), ServiceError> {
+    let mut settings = load_settings(&args.source);
+    if let Some(backend) = args.backend {
+        settings.wallpaper_backend = Some(backend.into());
+    }
+
+    let image = resolve_images(&settings, &args.source, 1)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| ServiceError::Parse("No image returned".to_string()))?;
+    let cached = DiskCache::for_settings(&settings).fetch(&image.url).await?;
+
+    let path = runtime::spawn_blocking(move || set_wallpaper(&cached, &settings)).await??;
+    eprintln!("Wallpaper set to {} ({})", image.url, path.display());
+    Ok(())
+}
+
+/// Picks `count` distinct images, either from `--tag` or from the categories
+/// selected in the settings.
+async fn resolve_images(settings: &UserSettings, source: &SourceArgs, count: usize) -> Result<Vec<ImageRecord>, ServiceError> {
+    let mut images = Vec::new();
+    let mut seen = HashSet::new();
+    let mut empty_rounds = 0;
+
+    while images.len() < count && empty_rounds < MAX_EMPTY_ROUNDS {
+        let batch = match &source.tag {
+            Some(tag) => {
+                let exclude: Vec<String> = seen.iter().cloned().collect();
+                fetch_waifu_images_async(settings, tag, source.nsfw, count - images.len(), &exclude).await?
+            }
+            None => vec![fetch_waifu_image_async(settings).await?],
+        };
+
+        let before = images.len();
+        for image in batch {
+            if images.len() < count && seen.insert(image.url.clone()) {
+                images.push(image);
+            }
+        }
+        if images.len() == before {
+            empty_rounds += 1;
+        } else {
+            empty_rounds = 0;
+        }
+    }
+
+    if images.len() < count {
+        eprintln!("Only found {} of {} requested images", images.len(), count);
+    }
+    Ok(images)
+}
+
+async fn save_image(disk_cache: &DiskCache, image: &ImageRecord, out: &Path) -> Result<PathBuf, ServiceError> {
+    let cached = disk_cache.fetch(&image.url).await?;
+
+    let file_name = render_file_name("{name}.{ext}", image, &file_extension(&cached), 1, 1);
+    copy_to_new_file(&cached, out, &file_name, &Mutex::new(HashSet::new())).await
+}
@@ -1,18 +1,40 @@
+use clap::Parser;
 use gtk4::prelude::*;
-use gtk4::Application;
+use gtk4::{gio, Application};
 
+mod cli;
 mod models;
+mod paths;
 mod services;
 mod ui;
 
+use cli::Cli;
 use ui::build_main_window;
 
 fn main() {
+    let cli = Cli::parse();
+    if let Some(home) = cli.home {
+        paths::set_root_override(home);
+    }
+    if let Some(command) = cli.command {
+        std::process::exit(cli::run(command));
+    }
+
+    // With its own data directory this is a separate instance rather than a
+    // second window of the one already running.
+    let flags = if paths::has_root_override() {
+        gio::ApplicationFlags::NON_UNIQUE
+    } else {
+        gio::ApplicationFlags::empty()
+    };
     let app = Application::builder()
         .application_id("com.waifugenerator.app")
+        .flags(flags)
         .build();
 
     app.connect_activate(build_main_window);
+    app.connect_shutdown(|_| services::perceptual::flush_now());
 
-    app.run();
+    // Arguments were already handled by clap; GTK would reject them.
+    app.run_with_args::<&str>(&[]);
 }
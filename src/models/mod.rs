@@ -1,26 +1,24 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Deserialize, Serialize)]
+use crate::paths;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct WaifuTags {
     pub versatile: Vec<String>,
     pub nsfw: Vec<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct WaifuImage {
     pub signature: String,
     pub extension: String,
     pub image_id: u32,
     pub favorites: u32,
     pub dominant_color: String,
-    pub source: String,
-    pub artist: String,
-    pub artist_id: u32,
-    pub name: String,
-    pub patreon: Option<String>,
-    pub pixiv: String,
-    pub twitter: String,
-    pub deviant_art: Option<String>,
+    pub source: Option<String>,
+    pub artist: Option<WaifuArtist>,
     pub uploaded_at: String,
     pub liked_at: Option<String>,
     pub is_nsfw: bool,
@@ -32,7 +30,17 @@ pub struct WaifuImage {
     pub tags: Vec<WaifuTag>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WaifuArtist {
+    pub artist_id: u32,
+    pub name: String,
+    pub patreon: Option<String>,
+    pub pixiv: Option<String>,
+    pub twitter: Option<String>,
+    pub deviant_art: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct WaifuTag {
     pub tag_id: u32,
     pub name: String,
@@ -40,35 +48,429 @@ pub struct WaifuTag {
     pub is_nsfw: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct WaifuImageResponse {
     pub images: Vec<WaifuImage>,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    #[default]
+    WaifuPics,
+    WaifuIm,
+}
+
+impl ProviderKind {
+    pub const ALL: [ProviderKind; 2] = [ProviderKind::WaifuPics, ProviderKind::WaifuIm];
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ProviderKind::WaifuPics => "waifu.pics",
+            ProviderKind::WaifuIm => "waifu.im",
+        }
+    }
+}
+
+/// A way of applying the desktop wallpaper. `UserSettings::wallpaper_backend`
+/// being `None` means it is detected from the running desktop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WallpaperBackend {
+    Gnome,
+    Kde,
+    Swww,
+    Swaybg,
+    Feh,
+}
+
+impl WallpaperBackend {
+    pub const ALL: [WallpaperBackend; 5] = [
+        WallpaperBackend::Gnome,
+        WallpaperBackend::Kde,
+        WallpaperBackend::Swww,
+        WallpaperBackend::Swaybg,
+        WallpaperBackend::Feh,
+    ];
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            WallpaperBackend::Gnome => "GNOME",
+            WallpaperBackend::Kde => "KDE Plasma",
+            WallpaperBackend::Swww => "swww",
+            WallpaperBackend::Swaybg => "swaybg",
+            WallpaperBackend::Feh => "feh",
+        }
+    }
+}
+
+/// Where the slideshow takes its next image from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlideshowSource {
+    #[default]
+    Fresh,
+    History,
+    Favorites,
+}
+
+impl SlideshowSource {
+    pub const ALL: [SlideshowSource; 3] = [
+        SlideshowSource::Fresh,
+        SlideshowSource::History,
+        SlideshowSource::Favorites,
+    ];
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            SlideshowSource::Fresh => "New images",
+            SlideshowSource::History => "History",
+            SlideshowSource::Favorites => "Favorites",
+        }
+    }
+}
+
+/// How the category of each new image is chosen among the selected ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagStrategy {
+    /// SFW or NSFW according to `UserSettings::nsfw_ratio`, then a category of
+    /// that side according to its weight.
+    #[default]
+    Weighted,
+    /// Every selected category equally likely, whichever side it is on.
+    Uniform,
+    /// Each selected category in turn.
+    RoundRobin,
+    /// Categories drawn without replacement from a shuffled bag holding each
+    /// one as many times as its weight, refilled once empty.
+    ShuffleBag,
+}
+
+impl TagStrategy {
+    pub const ALL: [TagStrategy; 4] = [
+        TagStrategy::Weighted,
+        TagStrategy::Uniform,
+        TagStrategy::RoundRobin,
+        TagStrategy::ShuffleBag,
+    ];
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            TagStrategy::Weighted => "Weighted random",
+            TagStrategy::Uniform => "Uniform over all categories",
+            TagStrategy::RoundRobin => "Round robin",
+            TagStrategy::ShuffleBag => "Shuffle bag",
+        }
+    }
+}
+
+/// Relative weight of each category, used by the weighted strategy and the
+/// shuffle bag. Categories that are not listed weigh 1.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagWeights {
+    pub versatile: BTreeMap<String, u32>,
+    pub nsfw: BTreeMap<String, u32>,
+}
+
+impl TagWeights {
+    pub fn get(&self, tag: &str, is_nsfw: bool) -> u32 {
+        let weights = if is_nsfw { &self.nsfw } else { &self.versatile };
+        weights.get(tag).copied().unwrap_or(1).max(1)
+    }
+
+    pub fn set(&mut self, tag: &str, is_nsfw: bool, weight: u32) {
+        let weights = if is_nsfw { &mut self.nsfw } else { &mut self.versatile };
+        if weight <= 1 {
+            weights.remove(tag);
+        } else {
+            weights.insert(tag.to_string(), weight);
+        }
+    }
+}
+
+/// Keyboard accelerator for each main-window action, in GTK accelerator syntax
+/// (for example `<Control>s` or `Right`). An empty string leaves the action
+/// without a shortcut.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub prev: String,
+    pub next: String,
+    pub save: String,
+    pub favorite: String,
+    pub fullscreen: String,
+    pub settings: String,
+}
+
+impl KeyBindings {
+    /// Names of the bound `app.*` actions.
+    pub const ACTIONS: [&'static str; 6] = ["prev", "next", "save", "favorite", "fullscreen", "settings"];
+
+    pub fn get(&self, action: &str) -> Option<&str> {
+        let accel = match action {
+            "prev" => &self.prev,
+            "next" => &self.next,
+            "save" => &self.save,
+            "favorite" => &self.favorite,
+            "fullscreen" => &self.fullscreen,
+            "settings" => &self.settings,
+            _ => return None,
+        };
+        Some(accel.as_str())
+    }
+
+    pub fn set(&mut self, action: &str, accel: String) {
+        let slot = match action {
+            "prev" => &mut self.prev,
+            "next" => &mut self.next,
+            "save" => &mut self.save,
+            "favorite" => &mut self.favorite,
+            "fullscreen" => &mut self.fullscreen,
+            "settings" => &mut self.settings,
+            _ => return,
+        };
+        *slot = accel;
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            prev: "Left".to_string(),
+            next: "Right".to_string(),
+            save: "<Control>s".to_string(),
+            favorite: "<Control>d".to_string(),
+            fullscreen: "F11".to_string(),
+            settings: "<Control>comma".to_string(),
+        }
+    }
+}
+
+/// An image returned by a provider, together with everything known about it.
+///
+/// waifu.pics only gives us a URL, so `details` is only filled in for providers
+/// that return full metadata (waifu.im).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageRecord {
+    pub url: String,
+    pub tag: String,
+    pub is_nsfw: bool,
+    pub provider: ProviderKind,
+    pub details: Option<WaifuImage>,
+}
+
+impl ImageRecord {
+    pub fn new(url: String, tag: &str, is_nsfw: bool, provider: ProviderKind) -> Self {
+        Self {
+            url,
+            tag: tag.to_string(),
+            is_nsfw,
+            provider,
+            details: None,
+        }
+    }
+
+    pub fn with_details(tag: &str, provider: ProviderKind, image: WaifuImage) -> Self {
+        Self {
+            url: image.url.clone(),
+            tag: tag.to_string(),
+            is_nsfw: image.is_nsfw,
+            provider,
+            details: Some(image),
+        }
+    }
+
+    pub fn artist_name(&self) -> Option<&str> {
+        self.details
+            .as_ref()
+            .and_then(|d| d.artist.as_ref())
+            .map(|a| a.name.as_str())
+    }
+
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        self.details.as_ref().map(|d| (d.width, d.height))
+    }
+
+    /// One-line description such as "Neko (SFW) · 1920×1080 · by Artist".
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} ({})",
+            self.tag,
+            if self.is_nsfw { "NSFW" } else { "SFW" }
+        );
+        if let Some((width, height)) = self.dimensions() {
+            summary.push_str(&format!(" · {}×{}", width, height));
+        }
+        if let Some(artist) = self.artist_name() {
+            summary.push_str(&format!(" · by {}", artist));
+        }
+        summary
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserSettings {
+    #[serde(default)]
+    pub provider: ProviderKind,
     pub selected_versatile: Vec<String>,
     pub selected_nsfw: Vec<String>,
+    #[serde(default)]
+    pub tag_strategy: TagStrategy,
+    /// Share of images taken from the NSFW categories when both sides have
+    /// some selected, between 0 and 1. Only used by the weighted strategy.
+    #[serde(default = "default_nsfw_ratio")]
+    pub nsfw_ratio: f64,
+    #[serde(default)]
+    pub tag_weights: TagWeights,
+    #[serde(default = "default_cache_max_mb")]
+    pub cache_max_mb: u64,
+    #[serde(default = "default_prefetch_count")]
+    pub prefetch_count: usize,
+    /// Memory for decoded images kept around for quick history navigation.
+    #[serde(default = "default_texture_memory_mb")]
+    pub texture_memory_mb: u64,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default = "default_requests_per_minute")]
+    pub requests_per_minute: u32,
+    /// How many times a random pick is rolled again when it returns an image
+    /// that was already seen. Zero accepts repeats.
+    #[serde(default = "default_duplicate_rerolls")]
+    pub duplicate_rerolls: u32,
+    /// Also roll again when a pick merely looks like an image already seen.
+    #[serde(default)]
+    pub skip_similar_images: bool,
+    #[serde(default)]
+    pub wallpaper_backend: Option<WallpaperBackend>,
+    #[serde(default = "default_slideshow_interval_secs")]
+    pub slideshow_interval_secs: u32,
+    #[serde(default)]
+    pub slideshow_source: SlideshowSource,
+    #[serde(default)]
+    pub key_bindings: KeyBindings,
+    #[serde(default = "default_export_template")]
+    pub export_template: String,
+    #[serde(default = "default_export_concurrency")]
+    pub export_concurrency: usize,
+    #[serde(default = "default_save_template")]
+    pub save_template: String,
+    /// Folder images are saved to without asking; `None` opens a file dialog.
+    #[serde(default)]
+    pub save_dir: Option<PathBuf>,
+    /// Whether animated images and videos start over when they end.
+    #[serde(default = "default_loop_media")]
+    pub loop_media: bool,
+}
+
+fn default_nsfw_ratio() -> f64 {
+    0.5
+}
+
+fn default_cache_max_mb() -> u64 {
+    500
+}
+
+fn default_prefetch_count() -> usize {
+    3
+}
+
+fn default_texture_memory_mb() -> u64 {
+    256
+}
+
+fn default_requests_per_minute() -> u32 {
+    60
+}
+
+fn default_duplicate_rerolls() -> u32 {
+    5
+}
+
+fn default_slideshow_interval_secs() -> u32 {
+    10
+}
+
+fn default_export_template() -> String {
+    "{index}_{tag}_{name}.{ext}".to_string()
+}
+
+fn default_export_concurrency() -> usize {
+    4
+}
+
+fn default_save_template() -> String {
+    "{tag}_{id}.{ext}".to_string()
+}
+
+fn default_loop_media() -> bool {
+    true
+}
+
+/// How failed requests are retried: exponential backoff starting at
+/// `initial_backoff_ms`, doubled after each attempt and capped at `max_backoff_ms`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 8000,
+            jitter: true,
+        }
+    }
 }
 
 impl Default for UserSettings {
     fn default() -> Self {
         Self {
+            provider: ProviderKind::default(),
             selected_versatile: vec!["waifu".to_string()],
             selected_nsfw: vec![],
+            tag_strategy: TagStrategy::default(),
+            nsfw_ratio: default_nsfw_ratio(),
+            tag_weights: TagWeights::default(),
+            cache_max_mb: default_cache_max_mb(),
+            prefetch_count: default_prefetch_count(),
+            texture_memory_mb: default_texture_memory_mb(),
+            retry: RetryPolicy::default(),
+            requests_per_minute: default_requests_per_minute(),
+            duplicate_rerolls: default_duplicate_rerolls(),
+            skip_similar_images: false,
+            wallpaper_backend: None,
+            slideshow_interval_secs: default_slideshow_interval_secs(),
+            slideshow_source: SlideshowSource::default(),
+            key_bindings: KeyBindings::default(),
+            export_template: default_export_template(),
+            export_concurrency: default_export_concurrency(),
+            save_template: default_save_template(),
+            save_dir: None,
+            loop_media: default_loop_media(),
         }
     }
 }
 
 impl UserSettings {
-    pub fn save_to_file(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn save_to_file(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
         let json = serde_json::to_string_pretty(self)?;
         std::fs::write(path, json)?;
         Ok(())
     }
 
-    pub fn load_from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        if std::path::Path::new(path).exists() {
+    pub fn load_from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if path.exists() {
             let json = std::fs::read_to_string(path)?;
             let settings: UserSettings = serde_json::from_str(&json)?;
             Ok(settings)
@@ -77,17 +479,55 @@ impl UserSettings {
         }
     }
 
-    pub fn default_config_path() -> String {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        format!("{}/.config/waifu-generator/settings.json", home)
+    /// Whether `image` could have been produced by the current provider and
+    /// category selection.
+    pub fn matches(&self, image: &ImageRecord) -> bool {
+        let selected = if image.is_nsfw {
+            &self.selected_nsfw
+        } else {
+            &self.selected_versatile
+        };
+        image.provider == self.provider && selected.contains(&image.tag)
+    }
+
+    /// `settings.json` in the configuration directory (see `paths::config_dir`).
+    pub fn default_config_path() -> PathBuf {
+        paths::settings_file()
+    }
+}
+
+/// Maximum number of abandoned timelines `ImageCache` keeps around.
+pub const MAX_HISTORY_BRANCHES: usize = 10;
+
+/// A timeline that was left behind when a new image was added while browsing
+/// back in history.
+#[derive(Debug, Clone)]
+pub struct HistoryBranch {
+    /// The whole timeline as it was when it got abandoned.
+    pub images: Vec<ImageRecord>,
+    /// How many images at the start of `images` are shared with the timeline
+    /// that replaced it.
+    pub shared: usize,
+}
+
+impl HistoryBranch {
+    /// The images that only exist in this branch.
+    pub fn discarded(&self) -> &[ImageRecord] {
+        &self.images[self.shared.min(self.images.len())..]
+    }
+
+    /// The image the branch diverged from, if it is still known.
+    pub fn parent(&self) -> Option<&ImageRecord> {
+        self.shared.checked_sub(1).and_then(|index| self.images.get(index))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct ImageCache {
-    pub images: Vec<String>,
+    pub images: Vec<ImageRecord>,
     pub current_index: usize,
     pub max_size: usize,
+    pub branches: Vec<HistoryBranch>,
 }
 
 impl ImageCache {
@@ -96,24 +536,63 @@ impl ImageCache {
             images: Vec::new(),
             current_index: 0,
             max_size,
+            branches: Vec::new(),
         }
     }
     
-    pub fn add_image(&mut self, url: String) {
-        if self.current_index < self.images.len() {
+    /// Appends `image` after the cursor and moves the cursor to it. Images that
+    /// were ahead of the cursor are kept as a branch instead of being dropped,
+    /// and an earlier copy of the same image is moved rather than duplicated.
+    pub fn add_image(&mut self, image: ImageRecord) {
+        if self.current_index + 1 < self.images.len() {
+            self.save_branch(HistoryBranch {
+                images: self.images.clone(),
+                shared: self.current_index + 1,
+            });
             self.images.truncate(self.current_index + 1);
         }
         
-        self.images.push(url);
+        self.images.retain(|existing| existing.url != image.url);
+        self.images.push(image);
         
         if self.images.len() > self.max_size {
-            self.images.remove(0);
-        } else {
-            self.current_index = self.images.len() - 1;
+            let excess = self.images.len() - self.max_size;
+            self.images.drain(..excess);
         }
+        self.current_index = self.images.len().saturating_sub(1);
     }
     
-    pub fn get_current_image(&self) -> Option<&String> {
+    /// Switches to the branch at `index`, keeping the current timeline as a
+    /// branch in its place. The cursor moves to the first image that only
+    /// existed in the restored branch.
+    pub fn restore_branch(&mut self, index: usize) -> Option<&ImageRecord> {
+        if index >= self.branches.len() {
+            return None;
+        }
+        let branch = self.branches.remove(index);
+        
+        let shared = branch
+            .parent()
+            .and_then(|parent| self.images.iter().rposition(|image| image.url == parent.url))
+            .map_or(0, |position| position + 1);
+        if shared < self.images.len() {
+            let current = std::mem::take(&mut self.images);
+            self.save_branch(HistoryBranch { images: current, shared });
+        }
+        
+        self.images = branch.images;
+        self.current_index = branch.shared.min(self.images.len().saturating_sub(1));
+        self.get_current_image()
+    }
+    
+    fn save_branch(&mut self, branch: HistoryBranch) {
+        self.branches.push(branch);
+        if self.branches.len() > MAX_HISTORY_BRANCHES {
+            self.branches.remove(0);
+        }
+    }
+    
+    pub fn get_current_image(&self) -> Option<&ImageRecord> {
         self.images.get(self.current_index)
     }
     
@@ -126,7 +605,7 @@ impl ImageCache {
     }
     
     
-    pub fn go_previous(&mut self) -> Option<&String> {
+    pub fn go_previous(&mut self) -> Option<&ImageRecord> {
         if self.can_go_previous() {
             self.current_index -= 1;
             self.get_current_image()
@@ -135,7 +614,7 @@ impl ImageCache {
         }
     }
     
-    pub fn go_next(&mut self) -> Option<&String> {
+    pub fn go_next(&mut self) -> Option<&ImageRecord> {
         if self.can_go_next() {
             self.current_index += 1;
             self.get_current_image()
@@ -145,3 +624,168 @@ impl ImageCache {
     }
     
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str) -> ImageRecord {
+        ImageRecord::new(format!("https://example.com/{}.png", name), "waifu", false, ProviderKind::WaifuPics)
+    }
+
+    fn cache_with(max_size: usize, names: &[&str]) -> ImageCache {
+        let mut cache = ImageCache::new(max_size);
+        for name in names {
+            cache.add_image(record(name));
+        }
+        cache
+    }
+
+    fn name(image: &ImageRecord) -> &str {
+        image.url.trim_start_matches("https://example.com/").trim_end_matches(".png")
+    }
+
+    fn urls(images: &[ImageRecord]) -> Vec<&str> {
+        images.iter().map(name).collect()
+    }
+
+    fn current(cache: &ImageCache) -> &str {
+        name(cache.get_current_image().expect("cache has a current image"))
+    }
+
+    #[test]
+    fn empty_cache_has_no_current_image() {
+        let mut cache = ImageCache::new(3);
+        assert!(cache.get_current_image().is_none());
+        assert!(!cache.can_go_previous());
+        assert!(!cache.can_go_next());
+        assert!(cache.go_previous().is_none());
+        assert!(cache.go_next().is_none());
+    }
+
+    #[test]
+    fn adding_moves_cursor_to_newest_image() {
+        let cache = cache_with(3, &["a", "b"]);
+        assert_eq!(cache.current_index, 1);
+        assert_eq!(current(&cache), "b");
+        assert!(cache.can_go_previous());
+        assert!(!cache.can_go_next());
+    }
+
+    #[test]
+    fn eviction_drops_oldest_and_keeps_cursor_on_newest() {
+        let cache = cache_with(3, &["a", "b", "c", "d", "e"]);
+        assert_eq!(urls(&cache.images), ["c", "d", "e"]);
+        assert_eq!(cache.current_index, 2);
+        assert_eq!(current(&cache), "e");
+    }
+
+    #[test]
+    fn navigation_stops_at_both_ends() {
+        let mut cache = cache_with(3, &["a", "b", "c"]);
+        assert_eq!(cache.go_previous().map(name), Some("b"));
+        assert!(cache.go_previous().is_some());
+        assert!(cache.go_previous().is_none());
+        assert_eq!(current(&cache), "a");
+
+        assert!(cache.go_next().is_some());
+        assert!(cache.go_next().is_some());
+        assert!(cache.go_next().is_none());
+        assert_eq!(current(&cache), "c");
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let cache = cache_with(0, &["a"]);
+        assert!(cache.images.is_empty());
+        assert!(cache.get_current_image().is_none());
+    }
+
+    #[test]
+    fn adding_a_known_image_moves_it_to_the_end() {
+        let cache = cache_with(3, &["a", "b", "a"]);
+        assert_eq!(urls(&cache.images), ["b", "a"]);
+        assert_eq!(current(&cache), "a");
+    }
+
+    #[test]
+    fn adding_from_the_middle_keeps_forward_images_as_a_branch() {
+        let mut cache = cache_with(5, &["a", "b", "c", "d"]);
+        cache.go_previous();
+        cache.go_previous();
+        cache.add_image(record("x"));
+
+        assert_eq!(urls(&cache.images), ["a", "b", "x"]);
+        assert_eq!(current(&cache), "x");
+        assert_eq!(cache.branches.len(), 1);
+        assert_eq!(urls(cache.branches[0].discarded()), ["c", "d"]);
+        assert_eq!(cache.branches[0].parent().map(name), Some("b"));
+    }
+
+    #[test]
+    fn adding_at_the_end_creates_no_branch() {
+        let cache = cache_with(5, &["a", "b", "c"]);
+        assert!(cache.branches.is_empty());
+    }
+
+    #[test]
+    fn eviction_after_branching_keeps_the_branch_intact() {
+        let mut cache = cache_with(3, &["a", "b", "c"]);
+        cache.go_previous();
+        cache.add_image(record("x"));
+        cache.add_image(record("y"));
+
+        assert_eq!(urls(&cache.images), ["b", "x", "y"]);
+        assert_eq!(urls(&cache.branches[0].images), ["a", "b", "c"]);
+        assert_eq!(urls(cache.branches[0].discarded()), ["c"]);
+    }
+
+    #[test]
+    fn restoring_a_branch_swaps_timelines() {
+        let mut cache = cache_with(5, &["a", "b", "c", "d"]);
+        cache.go_previous();
+        cache.go_previous();
+        cache.add_image(record("x"));
+
+        assert_eq!(cache.restore_branch(0).map(name), Some("c"));
+        assert_eq!(urls(&cache.images), ["a", "b", "c", "d"]);
+        assert_eq!(cache.current_index, 2);
+
+        assert_eq!(cache.branches.len(), 1);
+        assert_eq!(urls(cache.branches[0].discarded()), ["x"]);
+    }
+
+    #[test]
+    fn restoring_when_parent_was_evicted_keeps_whole_timeline() {
+        let mut cache = cache_with(2, &["a", "b"]);
+        cache.go_previous();
+        cache.add_image(record("x"));
+        cache.add_image(record("y"));
+        assert_eq!(urls(&cache.images), ["x", "y"]);
+
+        cache.restore_branch(0);
+        assert_eq!(urls(&cache.images), ["a", "b"]);
+        assert_eq!(current(&cache), "b");
+        assert_eq!(urls(cache.branches[0].discarded()), ["x", "y"]);
+    }
+
+    #[test]
+    fn restoring_an_unknown_branch_does_nothing() {
+        let mut cache = cache_with(3, &["a", "b"]);
+        assert!(cache.restore_branch(0).is_none());
+        assert_eq!(urls(&cache.images), ["a", "b"]);
+        assert_eq!(current(&cache), "b");
+    }
+
+    #[test]
+    fn oldest_branches_are_dropped_past_the_limit() {
+        let mut cache = cache_with(3, &["a", "b"]);
+        for i in 0..MAX_HISTORY_BRANCHES + 2 {
+            cache.go_previous();
+            cache.add_image(record(&format!("x{}", i)));
+        }
+
+        assert_eq!(cache.branches.len(), MAX_HISTORY_BRANCHES);
+        assert_eq!(urls(cache.branches[0].discarded()), ["x1"]);
+    }
+}
@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Environment variable that keeps every file of the application under one
+/// directory instead of the XDG locations, for portable installs and tests.
+pub const ROOT_ENV: &str = "WAIFU_GENERATOR_HOME";
+
+const APP_DIR: &str = "waifu-generator";
+
+static ROOT_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Keeps every file under `root`, taking precedence over `WAIFU_GENERATOR_HOME`.
+/// Only the first call has an effect, so it must happen before any path is used.
+pub fn set_root_override(root: PathBuf) {
+    if ROOT_OVERRIDE.set(root).is_err() {
+        eprintln!("Data directory was already set, ignoring the new one");
+    }
+}
+
+/// Settings, under `$XDG_CONFIG_HOME/waifu-generator`.
+pub fn config_dir() -> PathBuf {
+    app_dir("config", "XDG_CONFIG_HOME", &[".config"])
+}
+
+/// Disposable files such as downloaded images, under `$XDG_CACHE_HOME/waifu-generator`.
+pub fn cache_dir() -> PathBuf {
+    app_dir("cache", "XDG_CACHE_HOME", &[".cache"])
+}
+
+/// Files the user would miss, such as favorites, under `$XDG_DATA_HOME/waifu-generator`.
+pub fn data_dir() -> PathBuf {
+    app_dir("data", "XDG_DATA_HOME", &[".local", "share"])
+}
+
+/// History and other state worth keeping across sessions, under
+/// `$XDG_STATE_HOME/waifu-generator`.
+pub fn state_dir() -> PathBuf {
+    app_dir("state", "XDG_STATE_HOME", &[".local", "state"])
+}
+
+pub fn settings_file() -> PathBuf {
+    config_dir().join("settings.json")
+}
+
+/// `<root>/<name>` when the location is overridden, otherwise the application
+/// directory inside `$<xdg_var>` or, when that is unset or not absolute (which
+/// the spec says to ignore), inside the default location under the home
+/// directory.
+fn app_dir(name: &str, xdg_var: &str, home_default: &[&str]) -> PathBuf {
+    if let Some(root) = root_override() {
+        return root.join(name);
+    }
+
+    let base = std::env::var_os(xdg_var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .unwrap_or_else(|| home_default.iter().fold(glib::home_dir(), |dir, part| dir.join(part)));
+
+    base.join(APP_DIR)
+}
+
+/// Whether every file is kept under one directory given by `--home` or
+/// `WAIFU_GENERATOR_HOME`.
+pub fn has_root_override() -> bool {
+    root_override().is_some()
+}
+
+fn root_override() -> Option<PathBuf> {
+    ROOT_OVERRIDE.get().cloned().or_else(|| {
+        std::env::var_os(ROOT_ENV)
+            .filter(|root| !root.is_empty())
+            .map(PathBuf::from)
+    })
+}
@@ -0,0 +1,402 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+
+use crate::models::{RetryPolicy, UserSettings};
+use crate::paths;
+use crate::services::error::ServiceError;
+use crate::services::naming::detect_extension;
+use crate::services::retry::with_retry;
+use crate::services::runtime::http_client;
+
+/// Largest buffer reserved up front from an announced download size, so a
+/// bogus `Content-Length` cannot make us allocate huge amounts of memory.
+const MAX_PREALLOCATION: u64 = 64 * 1024 * 1024;
+
+/// How far along a download is. `total` is `None` when the size is unknown.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DownloadProgress {
+    pub received: u64,
+    pub total: Option<u64>,
+}
+
+/// Serializes every read-modify-write of `index.json` within this process.
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// Files in the cache directory missing from the index are deleted once they
+/// are this old. The delay leaves another instance time to add the file it is
+/// writing to the index.
+const ORPHAN_GRACE: Duration = Duration::from_secs(10 * 60);
+
+/// Reads save the access times they collect at most this often, so showing
+/// cached images does not rewrite `index.json` every time.
+const ACCESS_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Access times of cache hits not written to `index.json` yet. They are also
+/// applied before evicting, so eviction always sees them.
+static PENDING_ACCESS: Mutex<PendingAccess> = Mutex::new(PendingAccess {
+    accesses: Vec::new(),
+    last_saved: None,
+});
+
+struct PendingAccess {
+    accesses: Vec<(String, u64)>,
+    last_saved: Option<Instant>,
+}
+
+/// Content-addressed image cache.
+///
+/// Files are named after the SHA-256 of their URL and tracked in `index.json`
+/// together with the SHA-256 of their contents, so a truncated or tampered file
+/// is detected and downloaded again. When the cache grows past `max_bytes`, the
+/// least recently used images are removed.
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    retry: RetryPolicy,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheIndex {
+    entries: HashMap<String, CacheEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    file_name: String,
+    size: u64,
+    sha256: String,
+    last_access: u64,
+    /// Modification time of the file, in nanoseconds since the epoch, when its
+    /// digest was last checked. A file with the same size and time is trusted
+    /// without hashing it again.
+    #[serde(default)]
+    modified: u64,
+}
+
+impl DiskCache {
+    pub fn new(dir: PathBuf, max_bytes: u64, retry: RetryPolicy) -> Self {
+        Self { dir, max_bytes, retry }
+    }
+
+    /// The shared cache under `$XDG_CACHE_HOME/waifu-generator/images`, sized
+    /// and retrying downloads according to `settings`.
+    pub fn for_settings(settings: &UserSettings) -> Self {
+        Self::new(
+            paths::cache_dir().join("images"),
+            settings.cache_max_mb * 1024 * 1024,
+            settings.retry.clone(),
+        )
+    }
+
+    /// Returns the cached file for `url` and its content, if present and
+    /// intact. Files changed since their digest was recorded, for instance by
+    /// a crash while writing them, are checked again.
+    pub fn get(&self, url: &str) -> Option<(PathBuf, Vec<u8>)> {
+        let _guard = INDEX_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut index = self.load_index();
+        let key = url_key(url);
+
+        let entry = index.entries.get(&key)?.clone();
+        let path = self.dir.join(&entry.file_name);
+
+        let Some((bytes, modified)) = read_verified(&path, &entry) else {
+            eprintln!("Cached image failed integrity check, discarding: {}", url);
+            let _ = std::fs::remove_file(&path);
+            index.entries.remove(&key);
+            self.save_index(&index);
+            return None;
+        };
+
+        let mut pending = PENDING_ACCESS.lock().unwrap_or_else(|e| e.into_inner());
+        pending.accesses.push((key.clone(), now_secs()));
+        let rechecked = modified != entry.modified;
+        let save_due = pending
+            .last_saved
+            .is_none_or(|last_saved| last_saved.elapsed() >= ACCESS_SAVE_INTERVAL);
+        if rechecked || save_due {
+            if let Some(entry) = index.entries.get_mut(&key) {
+                entry.modified = modified;
+            }
+            apply_pending_access(&mut index, &mut pending);
+            self.save_index(&index);
+        }
+
+        Some((path, bytes))
+    }
+
+    /// Calls `visit` with the file and content of each of `urls` that is cached
+    /// and intact, reading the index once. Unlike `get`, this leaves the cache
+    /// as it is: access times are not updated and damaged files are skipped
+    /// rather than discarded.
+    pub fn peek_each<'a>(&self, urls: impl IntoIterator<Item = &'a str>, mut visit: impl FnMut(&'a str, PathBuf, Vec<u8>)) {
+        let index = {
+            let _guard = INDEX_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            self.load_index()
+        };
+
+        for url in urls {
+            let Some(entry) = index.entries.get(&url_key(url)) else {
+                continue;
+            };
+            let path = self.dir.join(&entry.file_name);
+            if let Some((bytes, _)) = read_verified(&path, entry) {
+                visit(url, path, bytes);
+            }
+        }
+    }
+
+    /// Returns the cached file for `url`, downloading it first if needed.
+    pub async fn fetch(&self, url: &str) -> Result<PathBuf, ServiceError> {
+        Ok(self.fetch_bytes(url).await?.0)
+    }
+
+    /// Like `fetch`, also returning the image data so it can be decoded
+    /// without reading the file back.
+    pub async fn fetch_bytes(&self, url: &str) -> Result<(PathBuf, Vec<u8>), ServiceError> {
+        self.fetch_with_progress(url, None, None).await
+    }
+
+    /// Like `fetch_bytes`, streaming the download and reporting how much of it
+    /// has arrived on `progress`. `expected_size` is used as the total when
+    /// the server does not send a `Content-Length`.
+    pub async fn fetch_with_progress(
+        &self,
+        url: &str,
+        expected_size: Option<u64>,
+        progress: Option<&watch::Sender<DownloadProgress>>,
+    ) -> Result<(PathBuf, Vec<u8>), ServiceError> {
+        let cache = self.clone();
+        let owned_url = url.to_string();
+        let cached = tokio::task::spawn_blocking(move || cache.get(&owned_url)).await?;
+
+        if let Some(cached) = cached {
+            eprintln!("Image served from cache: {}", url);
+            return Ok(cached);
+        }
+
+        eprintln!("Downloading image: {}", url);
+
+        // Image hosts are not the API, so a 429 from them must not hold up API
+        // requests.
+        let (bytes, content_type) = with_retry(&self.retry, None, || async move {
+            let response = http_client().get(url).send().await?;
+            let mut response = ServiceError::check_response(response)?;
+            let content_type = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+
+            let total = response.content_length().or(expected_size);
+            let mut bytes = Vec::with_capacity(total.unwrap_or(0).min(MAX_PREALLOCATION) as usize);
+            let report = |received: usize| {
+                if let Some(progress) = progress {
+                    progress.send_replace(DownloadProgress { received: received as u64, total });
+                }
+            };
+            report(0);
+            while let Some(chunk) = response.chunk().await? {
+                bytes.extend_from_slice(&chunk);
+                report(bytes.len());
+            }
+            Ok((bytes, content_type))
+        })
+        .await?;
+
+        let cache = self.clone();
+        let owned_url = url.to_string();
+        tokio::task::spawn_blocking(move || {
+            let path = cache.insert(&owned_url, &bytes, content_type.as_deref())?;
+            Ok((path, bytes))
+        })
+        .await?
+    }
+
+    /// Stores `bytes` as the content of `url` and evicts old entries if needed.
+    /// The file extension is taken from the data itself, then from
+    /// `content_type`, and only then from the URL.
+    pub fn insert(&self, url: &str, bytes: &[u8], content_type: Option<&str>) -> Result<PathBuf, ServiceError> {
+        let _guard = INDEX_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        std::fs::create_dir_all(&self.dir)?;
+
+        let key = url_key(url);
+        let extension = detect_extension(bytes, content_type)
+            .map(str::to_string)
+            .unwrap_or_else(|| url_extension(url));
+        let file_name = format!("{}.{}", key, extension);
+        let path = self.dir.join(&file_name);
+
+        std::fs::write(&path, bytes)?;
+
+        let mut index = self.load_index();
+        apply_pending_access(&mut index, &mut PENDING_ACCESS.lock().unwrap_or_else(|e| e.into_inner()));
+        if let Some(previous) = index.entries.get(&key)
+            && previous.file_name != file_name
+        {
+            let _ = std::fs::remove_file(self.dir.join(&previous.file_name));
+        }
+        index.entries.insert(key.clone(), CacheEntry {
+            url: url.to_string(),
+            file_name,
+            size: bytes.len() as u64,
+            sha256: hex_digest(bytes),
+            last_access: now_secs(),
+            modified: modified_nanos(&path).unwrap_or(0),
+        });
+
+        self.evict(&mut index, &key);
+        self.save_index(&index);
+
+        Ok(path)
+    }
+
+    /// Removes least recently used entries until the cache fits in `max_bytes`.
+    /// `keep` is never evicted so the image that was just added stays available.
+    /// Files the index does not know about, left behind when it was lost, are
+    /// removed as well since they would otherwise never be evicted.
+    fn evict(&self, index: &mut CacheIndex, keep: &str) {
+        self.remove_orphans(index);
+
+        let mut total: u64 = index.entries.values().map(|e| e.size).sum();
+        if total <= self.max_bytes {
+            return;
+        }
+
+        let mut by_age: Vec<(String, u64, u64)> = index
+            .entries
+            .iter()
+            .filter(|(key, _)| key.as_str() != keep)
+            .map(|(key, entry)| (key.clone(), entry.last_access, entry.size))
+            .collect();
+        by_age.sort_by_key(|(_, last_access, _)| *last_access);
+
+        for (key, _, size) in by_age {
+            if total <= self.max_bytes {
+                break;
+            }
+            if let Some(entry) = index.entries.remove(&key) {
+                eprintln!("Evicting cached image: {}", entry.url);
+                let _ = std::fs::remove_file(self.dir.join(&entry.file_name));
+                total = total.saturating_sub(size);
+            }
+        }
+    }
+
+    fn remove_orphans(&self, index: &CacheIndex) {
+        let Ok(files) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+
+        let known: HashSet<&str> = index.entries.values().map(|entry| entry.file_name.as_str()).collect();
+        for file in files.filter_map(|file| file.ok()) {
+            let file_name = file.file_name();
+            let file_name = file_name.to_string_lossy();
+            if file_name == "index.json" || known.contains(file_name.as_ref()) {
+                continue;
+            }
+
+            let old_enough = file
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age >= ORPHAN_GRACE));
+            if old_enough && file.path().is_file() {
+                eprintln!("Removing cached file missing from the index: {}", file_name);
+                let _ = std::fs::remove_file(file.path());
+            }
+        }
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.dir.join("index.json")
+    }
+
+    fn load_index(&self) -> CacheIndex {
+        std::fs::read_to_string(self.index_path())
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes `index` to a temporary file first, so a crash or a second
+    /// instance never leaves a truncated index behind.
+    fn save_index(&self, index: &CacheIndex) {
+        let path = self.index_path();
+        let tmp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        let result = serde_json::to_string(index)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(&tmp_path, json).map_err(|e| e.to_string()))
+            .and_then(|_| std::fs::rename(&tmp_path, &path).map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            eprintln!("Error while saving cache index: {}", e);
+        }
+    }
+}
+
+/// The content of `path` and its modification time, if it matches the size
+/// recorded in `entry` and, unless the file is unchanged since it was last
+/// checked, its digest.
+fn read_verified(path: &Path, entry: &CacheEntry) -> Option<(Vec<u8>, u64)> {
+    let modified = modified_nanos(path)?;
+    let bytes = std::fs::read(path).ok().filter(|bytes| bytes.len() as u64 == entry.size)?;
+    if modified != entry.modified && hex_digest(&bytes) != entry.sha256 {
+        return None;
+    }
+    Some((bytes, modified))
+}
+
+fn modified_nanos(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos() as u64)
+}
+
+/// Writes the access times collected by reads into `index`.
+fn apply_pending_access(index: &mut CacheIndex, pending: &mut PendingAccess) {
+    for (key, last_access) in pending.accesses.drain(..) {
+        if let Some(entry) = index.entries.get_mut(&key) {
+            entry.last_access = entry.last_access.max(last_access);
+        }
+    }
+    pending.last_saved = Some(Instant::now());
+}
+
+fn url_key(url: &str) -> String {
+    hex_digest(url.as_bytes())
+}
+
+fn url_extension(url: &str) -> String {
+    url.rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .filter(|ext| !ext.is_empty() && ext.len() <= 5 && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or_else(|| "img".to_string())
+}
+
+/// SHA-256 of an image file in hex, to recognize the same image under
+/// different URLs.
+pub fn content_hash(bytes: &[u8]) -> String {
+    hex_digest(bytes)
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
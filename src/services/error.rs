@@ -0,0 +1,240 @@
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Everything that can go wrong while talking to a provider or the image cache.
+#[derive(Debug, Clone)]
+pub enum ServiceError {
+    /// The request never got a response (DNS, connection, timeout, TLS...).
+    Network(String),
+    /// The server answered with a non-success status code.
+    HttpStatus(u16),
+    /// The server answered 429 Too Many Requests.
+    RateLimited { retry_after: Option<Duration> },
+    /// The response body could not be understood.
+    Parse(String),
+    /// No category is selected in the settings.
+    EmptySelection,
+    /// Reading or writing a local file failed.
+    Io(String),
+    /// The wallpaper backend is missing or refused the image.
+    Wallpaper(String),
+    /// The user stopped the download.
+    Cancelled,
+}
+
+impl ServiceError {
+    /// Turns an unsuccessful response into the matching error, passing
+    /// successful responses through untouched.
+    pub fn check_response(response: reqwest::Response) -> Result<reqwest::Response, ServiceError> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| parse_retry_after(value, SystemTime::now()));
+            return Err(ServiceError::RateLimited { retry_after });
+        }
+
+        Err(ServiceError::HttpStatus(status.as_u16()))
+    }
+
+    /// Whether trying the same request again later may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ServiceError::Network(_) | ServiceError::RateLimited { .. } => true,
+            ServiceError::HttpStatus(code) => *code == 408 || *code >= 500,
+            ServiceError::Parse(_)
+            | ServiceError::EmptySelection
+            | ServiceError::Io(_)
+            | ServiceError::Wallpaper(_)
+            | ServiceError::Cancelled => false,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ServiceError::Network(_) => "Connection problem",
+            ServiceError::HttpStatus(code) if *code >= 500 => "The image service is having trouble",
+            ServiceError::HttpStatus(_) => "The image service refused the request",
+            ServiceError::RateLimited { .. } => "Too many requests",
+            ServiceError::Parse(_) => "Unexpected response",
+            ServiceError::EmptySelection => "No category selected",
+            ServiceError::Io(_) => "Could not access local files",
+            ServiceError::Wallpaper(_) => "Could not set the wallpaper",
+            ServiceError::Cancelled => "Download cancelled",
+        }
+    }
+
+    /// A hint telling the user what they can do about the error.
+    pub fn guidance(&self) -> String {
+        match self {
+            ServiceError::Network(_) => {
+                "Check your internet connection, then try again.".to_string()
+            }
+            ServiceError::HttpStatus(404) => {
+                "This category may no longer exist. Pick other categories in the settings.".to_string()
+            }
+            ServiceError::HttpStatus(code) if *code >= 500 => {
+                "The server is down or overloaded. Wait a moment and try again.".to_string()
+            }
+            ServiceError::HttpStatus(_) => {
+                "Try again, or switch to another image source in the settings.".to_string()
+            }
+            ServiceError::RateLimited { retry_after: Some(delay) } => {
+                format!("The server asked us to slow down. Try again in {} seconds.", delay.as_secs().max(1))
+            }
+            ServiceError::RateLimited { retry_after: None } => {
+                "The server asked us to slow down. Wait a little before trying again.".to_string()
+            }
+            ServiceError::Parse(_) => {
+                "The image source may have changed its API. Try again or switch source in the settings.".to_string()
+            }
+            ServiceError::EmptySelection => {
+                "Open the settings and select at least one category.".to_string()
+            }
+            ServiceError::Io(_) => {
+                "Make sure the cache directory exists and that you have enough disk space.".to_string()
+            }
+            ServiceError::Wallpaper(_) => {
+                "Make sure the wallpaper tool is installed, or pick another backend in the settings.".to_string()
+            }
+            ServiceError::Cancelled => {
+                "Press Retry to download the image again.".to_string()
+            }
+        }
+    }
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::Network(e) => write!(f, "Request error: {}", e),
+            ServiceError::HttpStatus(code) => write!(f, "HTTP error: {}", code),
+            ServiceError::RateLimited { retry_after: Some(delay) } => {
+                write!(f, "Rate limited, retry after {}s", delay.as_secs())
+            }
+            ServiceError::RateLimited { retry_after: None } => write!(f, "Rate limited"),
+            ServiceError::Parse(e) => write!(f, "Parsing error: {}", e),
+            ServiceError::EmptySelection => write!(f, "No category selected"),
+            ServiceError::Io(e) => write!(f, "I/O error: {}", e),
+            ServiceError::Wallpaper(e) => write!(f, "Wallpaper error: {}", e),
+            ServiceError::Cancelled => write!(f, "Download cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for ServiceError {}
+
+/// Reads a `Retry-After` value, either a number of seconds or an HTTP date
+/// such as `Wed, 21 Oct 2015 07:28:00 GMT`, as a delay from `now`. A date
+/// already past means no wait.
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = parse_http_date(value)?;
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+/// Parses the IMF-fixdate form of an HTTP date, the only one servers may send.
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let [weekday, day, month, year, time, "GMT"] = parts.as_slice() else {
+        return None;
+    };
+    if !weekday.ends_with(',') {
+        return None;
+    }
+
+    let day: u64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|name| name == month)? as u64 + 1;
+    let year: u64 = year.parse().ok()?;
+    let mut clock = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (clock.next()??, clock.next()??, clock.next()??);
+    if clock.next().is_some() || !(1..=31).contains(&day) || year < 1970 || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    // Days since 1970-01-01 in the proleptic Gregorian calendar, counting
+    // years from March so the leap day comes last.
+    let (year, month) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+
+    let seconds = days * 86_400 + hours * 3_600 + minutes * 60 + seconds;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+impl From<reqwest::Error> for ServiceError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            ServiceError::Parse(e.to_string())
+        } else if let Some(status) = e.status() {
+            ServiceError::HttpStatus(status.as_u16())
+        } else {
+            ServiceError::Network(e.to_string())
+        }
+    }
+}
+
+impl From<std::io::Error> for ServiceError {
+    fn from(e: std::io::Error) -> Self {
+        ServiceError::Io(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ServiceError {
+    fn from(e: serde_json::Error) -> Self {
+        ServiceError::Parse(e.to_string())
+    }
+}
+
+impl From<tokio::task::JoinError> for ServiceError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        if e.is_cancelled() {
+            ServiceError::Cancelled
+        } else {
+            ServiceError::Io(format!("Background task failed: {}", e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_after_accepts_seconds() {
+        assert_eq!(parse_retry_after(" 120 ", SystemTime::now()), Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn retry_after_accepts_an_http_date() {
+        let date = parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+        assert_eq!(date, UNIX_EPOCH + Duration::from_secs(1_445_412_480));
+
+        let now = date - Duration::from_secs(90);
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now), Some(Duration::from_secs(90)));
+        let later = date + Duration::from_secs(90);
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", later), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_rejects_other_values() {
+        assert_eq!(parse_retry_after("soon", SystemTime::now()), None);
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 CET", SystemTime::now()), None);
+        assert_eq!(parse_retry_after("Wed, 32 Oct 2015 07:28:00 GMT", SystemTime::now()), None);
+    }
+}
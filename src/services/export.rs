@@ -0,0 +1,153 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, watch, Semaphore};
+use tokio::task::JoinSet;
+
+use crate::models::ImageRecord;
+use crate::services::disk_cache::DiskCache;
+use crate::services::error::ServiceError;
+use crate::services::favorites::FavoritesStore;
+use crate::services::naming::{file_extension, render_file_name, unique_path};
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub dir: PathBuf,
+    pub template: String,
+    pub concurrency: usize,
+}
+
+/// Reported once per image as the export progresses.
+#[derive(Debug)]
+pub enum ExportEvent {
+    Saved { path: PathBuf },
+    Failed { url: String, error: ServiceError },
+}
+
+/// Copies every image of `images` into `options.dir`, downloading the ones
+/// that are neither a favorite nor in the disk cache, with at most
+/// `options.concurrency` downloads at a time.
+///
+/// Each finished image is sent on `events`. Setting `cancel` to `true`, or
+/// dropping its sender, aborts the images still in progress and skips the
+/// remaining ones.
+pub async fn export_images(
+    images: Vec<ImageRecord>,
+    options: ExportOptions,
+    disk_cache: DiskCache,
+    mut cancel: watch::Receiver<bool>,
+    events: mpsc::UnboundedSender<ExportEvent>,
+) -> Result<(), ServiceError> {
+    tokio::fs::create_dir_all(&options.dir).await?;
+
+    let total = images.len();
+    let options = Arc::new(options);
+    // Names handed out so far, so concurrent images never pick the same file.
+    let taken = Arc::new(Mutex::new(HashSet::new()));
+    let favorites = Arc::new(FavoritesStore::load());
+    let semaphore = Arc::new(Semaphore::new(options.concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for (index, image) in images.into_iter().enumerate() {
+        let permit = tokio::select! {
+            permit = Arc::clone(&semaphore).acquire_owned() => permit.expect("export semaphore is never closed"),
+            _ = cancelled(&mut cancel) => break,
+        };
+
+        let options = Arc::clone(&options);
+        let taken = Arc::clone(&taken);
+        let disk_cache = disk_cache.clone();
+        let favorites = Arc::clone(&favorites);
+        let events = events.clone();
+        tasks.spawn(async move {
+            let _permit = permit;
+            let placement = (index + 1, total);
+            let event = match export_one(&image, placement, &options, &taken, &disk_cache, &favorites).await {
+                Ok(path) => ExportEvent::Saved { path },
+                Err(error) => ExportEvent::Failed { url: image.url, error },
+            };
+            let _ = events.send(event);
+        });
+    }
+
+    loop {
+        tokio::select! {
+            finished = tasks.join_next() => {
+                if finished.is_none() {
+                    break;
+                }
+            }
+            _ = cancelled(&mut cancel) => {
+                eprintln!("Export cancelled");
+                tasks.abort_all();
+                while tasks.join_next().await.is_some() {}
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolves once the export is cancelled or the cancel sender is dropped.
+async fn cancelled(cancel: &mut watch::Receiver<bool>) {
+    let _ = cancel.wait_for(|cancelled| *cancelled).await;
+}
+
+/// Copies `image` into the export directory under a name no other image of
+/// the export and no existing file uses. The extension comes from the image
+/// data itself, since URLs do not always carry the right one.
+async fn export_one(
+    image: &ImageRecord,
+    (index, total): (usize, usize),
+    options: &ExportOptions,
+    taken: &Mutex<HashSet<PathBuf>>,
+    disk_cache: &DiskCache,
+    favorites: &FavoritesStore,
+) -> Result<PathBuf, ServiceError> {
+    let source = match favorites.local_file(&image.url) {
+        Some(path) => path,
+        None => disk_cache.fetch(&image.url).await?,
+    };
+
+    let file_name = render_file_name(&options.template, image, &file_extension(&source), index, total);
+    copy_to_new_file(&source, &options.dir, &file_name, taken).await
+}
+
+/// Copies `source` into `dir` as `file_name`, or as `file_name` with a number
+/// added when that name is in `taken` or a file already has it. The target is
+/// created with `create_new`, so a file that appears in the meantime is never
+/// overwritten: the next number is tried instead. The chosen path is added to
+/// `taken`.
+pub async fn copy_to_new_file(
+    source: &Path,
+    dir: &Path,
+    file_name: &str,
+    taken: &Mutex<HashSet<PathBuf>>,
+) -> Result<PathBuf, ServiceError> {
+    loop {
+        let target = {
+            let mut taken = taken.lock().expect("export name lock is never poisoned");
+            let target = unique_path(dir, file_name, &taken);
+            taken.insert(target.clone());
+            target
+        };
+
+        let mut file = match tokio::fs::OpenOptions::new().write(true).create_new(true).open(&target).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        let copied = async {
+            let mut source = tokio::fs::File::open(source).await?;
+            tokio::io::copy(&mut source, &mut file).await?;
+            file.flush().await
+        };
+        if let Err(e) = copied.await {
+            let _ = tokio::fs::remove_file(&target).await;
+            return Err(e.into());
+        }
+        return Ok(target);
+    }
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::models::ImageRecord;
+use crate::paths;
+use crate::services::error::ServiceError;
+
+/// An image the user has kept, with its own copy of the file so it stays
+/// available offline and is never evicted from the image cache.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Favorite {
+    pub image: ImageRecord,
+    pub added_at: u64,
+    pub file_name: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FavoritesStore {
+    pub favorites: Vec<Favorite>,
+}
+
+impl FavoritesStore {
+    pub fn default_path() -> PathBuf {
+        paths::data_dir().join("favorites.json")
+    }
+
+    pub fn files_dir() -> PathBuf {
+        paths::data_dir().join("favorites")
+    }
+
+    pub fn load() -> Self {
+        std::fs::read_to_string(Self::default_path())
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), ServiceError> {
+        let path = Self::default_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    pub fn contains(&self, url: &str) -> bool {
+        self.favorites.iter().any(|favorite| favorite.image.url == url)
+    }
+
+    /// The local copy of the favorite for `url`, if there is one on disk.
+    pub fn local_file(&self, url: &str) -> Option<PathBuf> {
+        self.favorites
+            .iter()
+            .find(|favorite| favorite.image.url == url)
+            .map(|favorite| favorite.local_path())
+            .filter(|path| path.is_file())
+    }
+
+    /// Adds `image`, copying `cached_file` into the favorites directory.
+    pub fn add(&mut self, image: ImageRecord, cached_file: &Path) -> Result<(), ServiceError> {
+        if self.contains(&image.url) {
+            return Ok(());
+        }
+
+        let files_dir = Self::files_dir();
+        std::fs::create_dir_all(&files_dir)?;
+
+        let file_name = cached_file
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| ServiceError::Io("Cached image has no file name".to_string()))?;
+        std::fs::copy(cached_file, files_dir.join(&file_name))?;
+
+        let added_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        self.favorites.push(Favorite {
+            image,
+            added_at,
+            file_name,
+        });
+        Ok(())
+    }
+
+    /// Removes the favorite for `url` and its local file. Returns whether
+    /// anything was removed.
+    pub fn remove(&mut self, url: &str) -> bool {
+        let Some(position) = self.favorites.iter().position(|f| f.image.url == url) else {
+            return false;
+        };
+
+        let favorite = self.favorites.remove(position);
+        if let Err(e) = std::fs::remove_file(favorite.local_path()) {
+            eprintln!("Could not remove favorite file: {}", e);
+        }
+        true
+    }
+}
+
+impl Favorite {
+    pub fn local_path(&self) -> PathBuf {
+        FavoritesStore::files_dir().join(&self.file_name)
+    }
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::models::ImageRecord;
+use crate::paths;
+use crate::services::error::ServiceError;
+use crate::services::runtime;
+
+/// Once the log grows past `MAX_LOG_BYTES` it is compacted down to the most
+/// recent `MAX_HISTORY_ENTRIES` entries.
+const MAX_LOG_BYTES: u64 = 8 * 1024 * 1024;
+const MAX_HISTORY_ENTRIES: usize = 5000;
+
+/// Views recorded by `HistoryLog::append` and not written yet, oldest first.
+static PENDING: Mutex<Vec<HistoryEntry>> = Mutex::new(Vec::new());
+
+/// Serializes writes to the log within this process.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// One image the user has viewed. The record carries the URL, tag and provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub viewed_at: u64,
+    pub image: ImageRecord,
+    /// SHA-256 of the downloaded file, when it was known at the time of viewing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+}
+
+/// Append-only log of viewed images, stored as JSON lines so that recording a
+/// view never rewrites the whole file.
+pub struct HistoryLog;
+
+impl HistoryLog {
+    pub fn default_path() -> PathBuf {
+        paths::state_dir().join("history.jsonl")
+    }
+
+    /// Every entry in the log, oldest first. Lines that cannot be parsed are skipped.
+    pub fn load() -> Vec<HistoryEntry> {
+        let Ok(content) = std::fs::read_to_string(Self::default_path()) else {
+            return Vec::new();
+        };
+
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    /// The `count` most recent entries, oldest first.
+    pub fn recent(count: usize) -> Vec<HistoryEntry> {
+        let mut entries = Self::load();
+        let start = entries.len().saturating_sub(count);
+        entries.drain(..start);
+        entries
+    }
+
+    /// Records a view at the end of the log. Writing may compact the whole
+    /// log, so it happens on a worker thread; views are still written in the
+    /// order they were recorded.
+    pub fn append(image: &ImageRecord, content_hash: Option<&str>) {
+        lock(&PENDING).push(HistoryEntry {
+            viewed_at: now_secs(),
+            image: image.clone(),
+            content_hash: content_hash.map(str::to_string),
+        });
+
+        runtime::spawn_blocking(|| {
+            let _guard = lock(&WRITE_LOCK);
+            let entries = std::mem::take(&mut *lock(&PENDING));
+            if let Err(e) = Self::write(&entries) {
+                eprintln!("Error while writing history: {}", e);
+            }
+        });
+    }
+
+    fn write(entries: &[HistoryEntry]) -> Result<(), ServiceError> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let path = Self::default_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut lines = String::new();
+        for entry in entries {
+            lines.push_str(&serde_json::to_string(entry)?);
+            lines.push('\n');
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        file.write_all(lines.as_bytes())?;
+
+        if file.metadata()?.len() > MAX_LOG_BYTES {
+            Self::compact()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the log keeping only the most recent entries.
+    fn compact() -> Result<(), ServiceError> {
+        let entries = Self::recent(MAX_HISTORY_ENTRIES);
+        let mut content = String::new();
+        for entry in &entries {
+            content.push_str(&serde_json::to_string(entry)?);
+            content.push('\n');
+        }
+
+        let path = Self::default_path();
+        let tmp_path = path.with_extension("jsonl.tmp");
+        std::fs::write(&tmp_path, content)?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -1,108 +1,185 @@
-use crate::models::{WaifuTags, UserSettings};
+pub mod disk_cache;
+pub mod error;
+pub mod export;
+pub mod favorites;
+pub mod history;
+pub mod naming;
+pub mod perceptual;
+pub mod providers;
+pub mod retry;
+pub mod runtime;
+pub mod seen;
+pub mod similar;
+pub mod tag_cache;
+pub mod tag_picker;
+pub mod wallpaper;
 
-pub fn fetch_waifu_tags_sync() -> Result<WaifuTags, String> {
-    Ok(WaifuTags {
-        versatile: vec![
-            "waifu".to_string(),
-            "neko".to_string(),
-            "shinobu".to_string(),
-            "megumin".to_string(),
-            "bully".to_string(),
-            "cuddle".to_string(),
-            "cry".to_string(),
-            "hug".to_string(),
-            "awoo".to_string(),
-            "kiss".to_string(),
-            "lick".to_string(),
-            "pat".to_string(),
-            "smug".to_string(),
-            "bonk".to_string(),
-            "yeet".to_string(),
-            "blush".to_string(),
-            "smile".to_string(),
-            "wave".to_string(),
-            "highfive".to_string(),
-            "handhold".to_string(),
-            "nom".to_string(),
-            "bite".to_string(),
-            "glomp".to_string(),
-            "slap".to_string(),
-            "kill".to_string(),
-            "kick".to_string(),
-            "happy".to_string(),
-            "wink".to_string(),
-            "poke".to_string(),
-            "dance".to_string(),
-            "cringe".to_string(),
-        ],
-        nsfw: vec![
-            "waifu".to_string(),
-            "neko".to_string(),
-            "trap".to_string(),
-            "blowjob".to_string(),
-        ],
+use tokio::sync::watch;
+
+use crate::models::{ImageRecord, ProviderKind, RetryPolicy, WaifuTags, UserSettings};
+use disk_cache::{DiskCache, DownloadProgress};
+use error::ServiceError;
+use providers::ImageProvider;
+use retry::{api_rate_limiter, with_retry};
+use seen::SeenImages;
+use tag_picker::pick_tag;
+
+/// Images asked for per roll when avoiding repeats, so that one of them is
+/// likely to be new even when the server ignores the exclude list.
+const CANDIDATES_PER_ROLL: usize = 10;
+
+/// Most recently seen URLs sent as the provider's exclude list.
+const EXCLUDE_LIMIT: usize = 200;
+
+/// Returns the provider's categories, preferring a fresh on-disk copy, then the
+/// live tag endpoint, then a stale copy, and finally the bundled list.
+pub async fn fetch_waifu_tags_async(provider: ProviderKind) -> WaifuTags {
+    let cached = tag_cache::load(provider);
+
+    if let Some(cached) = cached.as_ref().filter(|c| c.is_fresh()) {
+        eprintln!("Using cached categories for {}", provider.display_name());
+        return cached.tags.clone();
+    }
+
+    let limiter = api_rate_limiter();
+    let result = with_retry(&RetryPolicy::default(), Some(limiter), || async move {
+        limiter.acquire().await;
+        provider.list_tags().await
     })
+    .await;
+
+    match result {
+        Ok(tags) => {
+            eprintln!("Fetched {} categories from {}", tags.versatile.len() + tags.nsfw.len(), provider.display_name());
+            let entry = tag_cache::CachedTags::new(tags);
+            if let Err(e) = tag_cache::store(provider, &entry) {
+                eprintln!("Error while caching categories: {}", e);
+            }
+            entry.tags
+        }
+        Err(e) => {
+            eprintln!("Could not fetch categories: {}", e);
+            match cached {
+                Some(stale) => {
+                    eprintln!("Using stale cached categories");
+                    stale.tags
+                }
+                None => {
+                    eprintln!("Using bundled categories");
+                    provider.fallback_tags()
+                }
+            }
+        }
+    }
 }
 
-pub async fn fetch_waifu_image_async(settings: &UserSettings) -> Result<String, String> {
-    use rand::seq::SliceRandom;
-    use rand::Rng;
+pub async fn fetch_waifu_image_async(settings: &UserSettings) -> Result<ImageRecord, ServiceError> {
+    let (selected_tag, is_nsfw) = pick_tag(settings)?;
     
-    if settings.selected_versatile.is_empty() && settings.selected_nsfw.is_empty() {
-        return Err("No category selected".to_string());
-    }
+    let limiter = api_rate_limiter();
+    limiter.set_rate(settings.requests_per_minute).await;
     
-    let mut rng = rand::thread_rng();
-    let (selected_tag, is_nsfw) = if !settings.selected_versatile.is_empty() && !settings.selected_nsfw.is_empty() {
-        if rng.gen_bool(0.5) {
-            let tag = settings.selected_versatile.choose(&mut rng).unwrap();
-            (tag.clone(), false)
-        } else {
-            let tag = settings.selected_nsfw.choose(&mut rng).unwrap();
-            (tag.clone(), true)
-        }
-    } else if !settings.selected_versatile.is_empty() {
-        let tag = settings.selected_versatile.choose(&mut rng).unwrap();
-        (tag.clone(), false)
-    } else {
-        let tag = settings.selected_nsfw.choose(&mut rng).unwrap();
-        (tag.clone(), true)
-    };
-    
-    let url = if is_nsfw {
-        format!("https://api.waifu.pics/nsfw/{}", selected_tag)
-    } else {
-        format!("https://api.waifu.pics/sfw/{}", selected_tag)
-    };
+    let provider = settings.provider;
+    let tag = &selected_tag;
+    let result = with_retry(&settings.retry, Some(limiter), move || async move {
+        limiter.acquire().await;
+        provider.fetch_random(tag, is_nsfw).await
+    })
+    .await;
     
-    println!("Request URL: {}", url);
-    println!("Randomly selected tag: {}", selected_tag);
-    println!("Type: {}", if is_nsfw { "NSFW" } else { "SFW" });
+    match result {
+        Ok(image) => {
+            eprintln!("Image found: {}", image.url);
+            Ok(image)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            Err(e)
+        }
+    }
+}
+
+/// Like `fetch_waifu_image_async`, but rolls again, up to
+/// `UserSettings::duplicate_rerolls` times, when the pick is an image in `seen`
+/// either by URL or by content, or with `UserSettings::skip_similar_images`
+/// when it merely looks like one. Seen images still in the disk cache whose
+/// perceptual hash is missing are hashed first so they are compared too. The image is downloaded into the disk cache to
+/// compare its content, reporting on `progress`, and its hash is returned
+/// along with it. Once the rolls run out, a repeat is accepted.
+pub async fn fetch_unseen_image_async(
+    settings: &UserSettings,
+    seen: &SeenImages,
+    progress: Option<&watch::Sender<DownloadProgress>>,
+) -> Result<(ImageRecord, Option<String>), ServiceError> {
+    let disk_cache = DiskCache::for_settings(settings);
+    let exclude = seen.recent_urls(EXCLUDE_LIMIT);
+    let mut repeat = None;
     
-    match reqwest::get(&url).await {
-        Ok(response) => {
-            if response.status().is_success() {
-                match response.json::<serde_json::Value>().await {
-                    Ok(json) => {
-                        if let Some(image_url) = json.get("url").and_then(|u| u.as_str()) {
-                            println!("Image found: {}", image_url);
-                            return Ok(image_url.to_string());
-                        }
-                        Err("No image URL found in response".to_string())
-                    }
-                    Err(e) => {
-                        println!("JSON parsing error: {}", e);
-                        Err(format!("JSON parsing error: {}", e))
-                    }
-                }
-            } else {
-                println!("HTTP error: {}", response.status());
-                Err(format!("HTTP error: {}", response.status()))
+    for _ in 0..=settings.duplicate_rerolls {
+        let (tag, is_nsfw) = pick_tag(settings)?;
+        let candidates = fetch_waifu_images_async(settings, &tag, is_nsfw, CANDIDATES_PER_ROLL, &exclude).await?;
+        
+        let Some(image) = candidates.iter().find(|image| !seen.contains_url(&image.url)).cloned() else {
+            eprintln!("Only already seen images returned, rolling again");
+            if repeat.is_none() {
+                repeat = candidates.into_iter().next().map(|image| (image, None));
             }
+            continue;
+        };
+        
+        let expected_size = image.details.as_ref().map(|details| details.byte_size as u64);
+        let (_, bytes) = disk_cache.fetch_with_progress(&image.url, expected_size, progress).await?;
+        let content_hash = disk_cache::content_hash(&bytes);
+        if seen.contains_hash(&content_hash) {
+            eprintln!("Already seen image under a new URL, rolling again: {}", image.url);
+            repeat = Some((image, Some(content_hash)));
+            continue;
         }
-        Err(e) => {
-            println!("Request error: {}", e);
-            Err(format!("Request error: {}", e))
+        
+        if settings.skip_similar_images && settings.duplicate_rerolls > 0 {
+            let unhashed = seen.without_perceptual_hash();
+            let cache = disk_cache.clone();
+            let owned_hash = content_hash.clone();
+            let (perceptual_hash, backfilled) = runtime::spawn_blocking(move || {
+                let backfilled = perceptual::hash_cached(&cache, &unhashed);
+                let hash = perceptual::hash_image(&owned_hash, &bytes);
+                perceptual::flush();
+                (hash, backfilled)
+            })
+            .await?;
+            let looks_seen = |hash: u64| seen.contains_similar(hash) || perceptual::is_similar_to_any(hash, &backfilled);
+            if perceptual_hash.is_some_and(looks_seen) {
+                eprintln!("Image looks like one already seen, rolling again: {}", image.url);
+                repeat = Some((image, Some(content_hash)));
+                continue;
+            }
         }
+        
+        eprintln!("Image found: {}", image.url);
+        return Ok((image, Some(content_hash)));
     }
-}
\ No newline at end of file
+    
+    eprintln!("No unseen image after {} rolls, showing a repeat", settings.duplicate_rerolls + 1);
+    repeat.ok_or_else(|| ServiceError::Parse("No image returned".to_string()))
+}
+
+/// Fetches up to `count` images of `tag`, in a single request where the
+/// provider supports it. Providers may return fewer images than asked for, and
+/// only some of them leave out the URLs in `exclude`.
+pub async fn fetch_waifu_images_async(
+    settings: &UserSettings,
+    tag: &str,
+    is_nsfw: bool,
+    count: usize,
+    exclude: &[String],
+) -> Result<Vec<ImageRecord>, ServiceError> {
+    let limiter = api_rate_limiter();
+    limiter.set_rate(settings.requests_per_minute).await;
+    
+    let provider = settings.provider;
+    with_retry(&settings.retry, Some(limiter), move || async move {
+        limiter.acquire().await;
+        provider.fetch_many(tag, is_nsfw, count, exclude).await
+    })
+    .await
+}
@@ -0,0 +1,265 @@
+use std::cell::OnceCell;
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::models::ImageRecord;
+
+/// Tokens understood by `render_file_name`, listed next to template fields.
+pub const TEMPLATE_TOKENS: &str =
+    "{tag} {artist} {id} {name} {provider} {nsfw} {date} {time} {timestamp} {index} {ext}";
+
+/// File extension for image data, from its magic bytes or, failing that,
+/// from the `Content-Type` the server sent.
+pub fn detect_extension(bytes: &[u8], content_type: Option<&str>) -> Option<&'static str> {
+    sniff_extension(bytes).or_else(|| content_type.and_then(content_type_extension))
+}
+
+/// Extension of the image stored at `path`, looking at its content first and
+/// at its current extension second.
+pub fn file_extension(path: &Path) -> String {
+    let mut header = [0u8; 16];
+    let sniffed = std::fs::File::open(path)
+        .and_then(|mut file| file.read(&mut header))
+        .ok()
+        .and_then(|read| sniff_extension(&header[..read]));
+
+    sniffed
+        .map(str::to_string)
+        .or_else(|| {
+            path.extension()
+                .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+                .filter(|ext| ext != "img")
+        })
+        .unwrap_or_else(|| "png".to_string())
+}
+
+fn sniff_extension(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("jpg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("webp")
+    } else if bytes.starts_with(b"BM") {
+        Some("bmp")
+    } else if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+        // The major brand tells apart the formats sharing the ISO container.
+        match &bytes[8..12] {
+            b"avif" | b"avis" => Some("avif"),
+            b"heic" | b"heix" | b"hevc" | b"hevx" => Some("heic"),
+            b"mif1" | b"msf1" => Some("heif"),
+            b"isom" | b"iso2" | b"iso4" | b"iso5" | b"iso6" | b"mp41" | b"mp42" | b"avc1" | b"dash" | b"M4V "
+            | b"mmp4" => Some("mp4"),
+            _ => None,
+        }
+    } else if bytes.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        Some("webm")
+    } else {
+        None
+    }
+}
+
+fn content_type_extension(content_type: &str) -> Option<&'static str> {
+    let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    match mime.as_str() {
+        "image/png" => Some("png"),
+        "image/jpeg" | "image/jpg" => Some("jpg"),
+        "image/gif" => Some("gif"),
+        "image/webp" => Some("webp"),
+        "image/bmp" => Some("bmp"),
+        "image/avif" => Some("avif"),
+        "image/heic" => Some("heic"),
+        "image/heif" => Some("heif"),
+        "video/mp4" => Some("mp4"),
+        "video/webm" => Some("webm"),
+        _ => None,
+    }
+}
+
+/// Expands the tokens of `template` for `image`, whose file has extension
+/// `ext`. `index` and `total` place the image within a batch (both 1 for a
+/// single save). The result is safe to use as a single file name.
+pub fn render_file_name(template: &str, image: &ImageRecord, ext: &str, index: usize, total: usize) -> String {
+    let url_name = image
+        .url
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default();
+    let name = match url_name.rsplit_once('.') {
+        Some((name, _)) if !name.is_empty() => name,
+        _ if url_name.is_empty() => "waifu",
+        _ => url_name,
+    };
+    let id = image
+        .details
+        .as_ref()
+        .map(|details| details.image_id.to_string())
+        .unwrap_or_else(|| name.to_string());
+    // Only read the clock when the template asks for it.
+    let now = OnceCell::new();
+    let format_now = |format: &str| {
+        now.get_or_init(|| glib::DateTime::now_local().ok())
+            .as_ref()
+            .and_then(|date| date.format(format).ok())
+            .map(|text| text.to_string())
+            .unwrap_or_default()
+    };
+    let width = total.to_string().len();
+
+    let rendered = expand_tokens(template, |token| {
+        Some(match token {
+            "index" => format!("{:0width$}", index, width = width),
+            "tag" => image.tag.clone(),
+            "artist" => image.artist_name().unwrap_or("unknown").to_string(),
+            "id" => id.clone(),
+            "name" => name.to_string(),
+            "provider" => image.provider.display_name().to_string(),
+            "nsfw" => if image.is_nsfw { "nsfw" } else { "sfw" }.to_string(),
+            "date" => format_now("%Y-%m-%d"),
+            "time" => format_now("%H-%M-%S"),
+            "timestamp" => format_now("%s"),
+            "ext" => ext.to_string(),
+            _ => return None,
+        })
+    });
+
+    let sanitized: String = rendered
+        .chars()
+        .map(|c| if c == '/' || c == '\\' || c.is_control() { '_' } else { c })
+        .collect();
+    let sanitized = sanitized.trim().trim_start_matches('.');
+    if sanitized.is_empty() {
+        format!("waifu_{}.{}", index, ext)
+    } else {
+        sanitized.to_string()
+    }
+}
+
+/// Replaces each `{token}` of `template` with `value(token)` in a single
+/// pass, so braces inside a substituted value are left as they are. Unknown
+/// tokens and unmatched braces are kept literally.
+fn expand_tokens(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find(['{', '}']) {
+            Some(end) if after.as_bytes()[end] == b'}' => {
+                match value(&after[..end]) {
+                    Some(text) => expanded.push_str(&text),
+                    None => expanded.push_str(&rest[start..start + end + 2]),
+                }
+                rest = &after[end + 1..];
+            }
+            _ => {
+                expanded.push('{');
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// `dir/file_name`, or `dir/stem (2).ext` and so on if that path exists on
+/// disk or is in `taken`.
+pub fn unique_path(dir: &Path, file_name: &str, taken: &HashSet<PathBuf>) -> PathBuf {
+    let (stem, ext) = match file_name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{}", ext)),
+        _ => (file_name, String::new()),
+    };
+
+    let mut candidate = dir.join(file_name);
+    let mut suffix = 2;
+    while taken.contains(&candidate) || candidate.exists() {
+        candidate = dir.join(format!("{} ({}){}", stem, suffix, ext));
+        suffix += 1;
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProviderKind;
+
+    fn image(url: &str, tag: &str) -> ImageRecord {
+        ImageRecord::new(url.to_string(), tag, false, ProviderKind::WaifuPics)
+    }
+
+    #[test]
+    fn tokens_are_expanded() {
+        let image = image("https://example.com/i/abc123.jpg", "waifu");
+        assert_eq!(
+            render_file_name("{tag}_{name}_{provider}_{nsfw}.{ext}", &image, "png", 1, 1),
+            "waifu_abc123_waifu.pics_sfw.png"
+        );
+    }
+
+    #[test]
+    fn index_is_padded_to_the_batch_size() {
+        let image = image("https://example.com/a.png", "neko");
+        assert_eq!(render_file_name("{index}-{tag}.{ext}", &image, "png", 7, 120), "007-neko.png");
+    }
+
+    #[test]
+    fn substituted_values_are_not_expanded_again() {
+        let image = image("https://example.com/{index}.png", "{ext}");
+        assert_eq!(render_file_name("{tag}-{name}.{ext}", &image, "gif", 3, 10), "{ext}-{index}.gif");
+    }
+
+    #[test]
+    fn unknown_tokens_and_stray_braces_are_kept() {
+        let image = image("https://example.com/a.png", "neko");
+        assert_eq!(render_file_name("{x}{{tag}}{.{ext}", &image, "png", 1, 1), "{x}{neko}{.png");
+    }
+
+    #[test]
+    fn separators_are_replaced_and_empty_names_get_a_default() {
+        let image = image("https://example.com/a.png", "cat/girl");
+        assert_eq!(render_file_name("{tag}.{ext}", &image, "png", 1, 1), "cat_girl.png");
+        assert_eq!(render_file_name("...", &image, "png", 4, 9), "waifu_4.png");
+    }
+
+    #[test]
+    fn unique_path_numbers_taken_names() {
+        let dir = Path::new("/nonexistent-waifu-generator-test");
+        let mut taken = HashSet::new();
+        assert_eq!(unique_path(dir, "a.png", &taken), dir.join("a.png"));
+
+        taken.insert(dir.join("a.png"));
+        taken.insert(dir.join("a (2).png"));
+        assert_eq!(unique_path(dir, "a.png", &taken), dir.join("a (3).png"));
+
+        taken.insert(dir.join("README"));
+        assert_eq!(unique_path(dir, "README", &taken), dir.join("README (2)"));
+    }
+
+    #[test]
+    fn file_types_are_sniffed_from_magic_bytes() {
+        assert_eq!(sniff_extension(b"\x89PNG\r\n\x1a\n...."), Some("png"));
+        assert_eq!(sniff_extension(&[0xFF, 0xD8, 0xFF, 0xE0]), Some("jpg"));
+        assert_eq!(sniff_extension(b"GIF89a......"), Some("gif"));
+        assert_eq!(sniff_extension(b"RIFF\0\0\0\0WEBPVP8 "), Some("webp"));
+        assert_eq!(sniff_extension(&[0x1A, 0x45, 0xDF, 0xA3, 0x01]), Some("webm"));
+        assert_eq!(sniff_extension(b"plain text"), None);
+    }
+
+    #[test]
+    fn iso_containers_are_told_apart_by_brand() {
+        let ftyp = |brand: &[u8; 4]| [b"\0\0\0\x18ftyp".as_slice(), brand].concat();
+        assert_eq!(sniff_extension(&ftyp(b"isom")), Some("mp4"));
+        assert_eq!(sniff_extension(&ftyp(b"mp42")), Some("mp4"));
+        assert_eq!(sniff_extension(&ftyp(b"avif")), Some("avif"));
+        assert_eq!(sniff_extension(&ftyp(b"heic")), Some("heic"));
+        assert_eq!(sniff_extension(&ftyp(b"mif1")), Some("heif"));
+        assert_eq!(sniff_extension(&ftyp(b"qt  ")), None);
+    }
+}
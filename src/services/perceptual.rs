@@ -0,0 +1,252 @@
+use gtk4::gdk_pixbuf;
+use gtk4::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::paths;
+use crate::services::disk_cache::{self, DiskCache};
+
+/// Hashes at most this many bits apart are considered the same picture, for
+/// instance a re-encoded, resized or slightly cropped copy.
+pub const SIMILAR_DISTANCE: u32 = 10;
+
+/// Images are decoded no larger than this before being reduced to the hash
+/// grid, which keeps decoding large files cheap.
+const DECODE_SIZE: i32 = 256;
+
+/// `flush` writes new hashes at most this often, since each write rewrites the
+/// whole index.
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+static INDEX: OnceLock<Mutex<PerceptualIndex>> = OnceLock::new();
+
+/// Perceptual hashes by content hash (see `disk_cache::content_hash`), so each
+/// distinct file is decoded once whether it sits in the image cache, among the
+/// favorites or in a folder images were saved to. Hashes are only computed
+/// where they are compared: when skipping similar images and when looking for
+/// similar ones.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PerceptualIndex {
+    hashes: HashMap<String, u64>,
+    #[serde(skip)]
+    dirty: bool,
+    #[serde(skip)]
+    last_saved: Option<Instant>,
+    /// URLs `hash_cached` already looked for in the image cache during this
+    /// run, with the content hash of the copy it found there.
+    #[serde(skip)]
+    cached_urls: HashMap<String, Option<String>>,
+}
+
+/// The perceptual hash of the file whose content hash is `content_hash`, if it
+/// was computed before.
+pub fn lookup(content_hash: &str) -> Option<u64> {
+    index().hashes.get(content_hash).copied()
+}
+
+/// The perceptual hash of `bytes`, computing and remembering it if needed.
+/// `None` for data that is not a still or animated image, such as videos.
+/// Call `flush` to write newly computed hashes to disk.
+pub fn hash_image(content_hash: &str, bytes: &[u8]) -> Option<u64> {
+    if let Some(hash) = lookup(content_hash) {
+        return Some(hash);
+    }
+
+    let hash = difference_hash(bytes)?;
+    let mut index = index();
+    index.hashes.insert(content_hash.to_string(), hash);
+    index.dirty = true;
+    Some(hash)
+}
+
+/// Writes the hashes computed since the last write to disk, unless that was
+/// less than `SAVE_INTERVAL` ago.
+pub fn flush() {
+    let mut index = index();
+    if index.last_saved.is_none_or(|last_saved| last_saved.elapsed() >= SAVE_INTERVAL) {
+        save(&mut index);
+    }
+}
+
+/// Writes the hashes computed since the last write to disk right away.
+pub fn flush_now() {
+    save(&mut index());
+}
+
+/// Computes the missing perceptual hashes of seen images, given as their URL
+/// and content hash if known, from their copy in `disk_cache`. Returns the
+/// content hash of each of them still cached, which for images seen without
+/// recording one is the only way to compare them. Each URL is read at most
+/// once per run. Reads files, so it must run off the main thread.
+pub fn hash_cached(disk_cache: &DiskCache, images: &[(String, Option<String>)]) -> Vec<String> {
+    let unchecked: Vec<&str> = {
+        let index = index();
+        images
+            .iter()
+            .map(|(url, _)| url.as_str())
+            .filter(|url| !index.cached_urls.contains_key(*url))
+            .collect()
+    };
+
+    if !unchecked.is_empty() {
+        eprintln!("Computing perceptual hashes of {} seen images", unchecked.len());
+    }
+    let mut found: HashMap<&str, String> = HashMap::new();
+    disk_cache.peek_each(unchecked.iter().copied(), |url, _, bytes| {
+        let content_hash = disk_cache::content_hash(&bytes);
+        hash_image(&content_hash, &bytes);
+        found.insert(url, content_hash);
+    });
+
+    let mut index = index();
+    for url in unchecked {
+        index.cached_urls.insert(url.to_string(), found.remove(url));
+    }
+    images
+        .iter()
+        .filter_map(|(url, _)| index.cached_urls.get(url).cloned().flatten())
+        .collect()
+}
+
+/// Forgets the hashes of every file but the ones in `keep`, so the index does
+/// not grow with images that are gone. Call `flush` to write the change.
+pub fn retain(keep: &HashSet<String>) {
+    let mut index = index();
+    let before = index.hashes.len();
+    index.hashes.retain(|content_hash, _| keep.contains(content_hash));
+    if index.hashes.len() != before {
+        index.dirty = true;
+    }
+}
+
+/// Whether any of the files in `content_hashes` looks like `hash`. Files whose
+/// perceptual hash was never computed are ignored.
+pub fn is_similar_to_any<'a>(hash: u64, content_hashes: impl IntoIterator<Item = &'a String>) -> bool {
+    let index = index();
+    content_hashes
+        .into_iter()
+        .filter_map(|content_hash| index.hashes.get(content_hash))
+        .any(|seen| distance(*seen, hash) <= SIMILAR_DISTANCE)
+}
+
+/// Number of bits that differ between two perceptual hashes.
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Groups `hashes` whose members are linked by chains of similar hashes,
+/// returning only the groups of two or more, as indices into `hashes`.
+pub fn cluster(hashes: &[u64]) -> Vec<Vec<usize>> {
+    let mut parents: Vec<usize> = (0..hashes.len()).collect();
+    for i in 0..hashes.len() {
+        for j in (i + 1)..hashes.len() {
+            if distance(hashes[i], hashes[j]) <= SIMILAR_DISTANCE {
+                let (root_i, root_j) = (find_root(&mut parents, i), find_root(&mut parents, j));
+                parents[root_j] = root_i;
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..hashes.len() {
+        let root = find_root(&mut parents, i);
+        groups.entry(root).or_default().push(i);
+    }
+
+    let mut clusters: Vec<Vec<usize>> = groups.into_values().filter(|group| group.len() > 1).collect();
+    clusters.sort_by_key(|group| group[0]);
+    clusters
+}
+
+fn find_root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// dHash: the image shrunk to a 9×8 grayscale grid, one bit per pair of
+/// horizontally adjacent cells telling whether brightness increases.
+fn difference_hash(bytes: &[u8]) -> Option<u64> {
+    let loader = gdk_pixbuf::PixbufLoader::new();
+    loader.connect_size_prepared(|loader, width, height| {
+        let longest = width.max(height);
+        if longest > DECODE_SIZE {
+            let scale = DECODE_SIZE as f64 / longest as f64;
+            let scaled_width = ((width as f64 * scale).round() as i32).max(1);
+            let scaled_height = ((height as f64 * scale).round() as i32).max(1);
+            loader.set_size(scaled_width, scaled_height);
+        }
+    });
+    let decoded = loader.write(bytes).and_then(|_| loader.close());
+    if decoded.is_err() {
+        return None;
+    }
+
+    let grid = loader.pixbuf()?.scale_simple(9, 8, gdk_pixbuf::InterpType::Tiles)?;
+    let pixels = grid.read_pixel_bytes();
+    let rowstride = grid.rowstride() as usize;
+    let channels = grid.n_channels() as usize;
+    let brightness = |x: usize, y: usize| {
+        let offset = y * rowstride + x * channels;
+        let (r, g, b) = (pixels[offset] as u32, pixels[offset + 1] as u32, pixels[offset + 2] as u32);
+        r * 299 + g * 587 + b * 114
+    };
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if brightness(x, y) < brightness(x + 1, y) {
+                hash |= 1;
+            }
+        }
+    }
+    Some(hash)
+}
+
+fn index() -> MutexGuard<'static, PerceptualIndex> {
+    INDEX
+        .get_or_init(|| Mutex::new(load_index()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Writes `index` to a temporary file first, so a crash or a second instance
+/// never leaves a truncated index behind.
+fn save(index: &mut PerceptualIndex) {
+    if !index.dirty {
+        return;
+    }
+
+    let path = index_path();
+    let tmp_path = path.with_extension("json.tmp");
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .map_err(|e| e.to_string())
+        .and_then(|_| serde_json::to_string(&*index).map_err(|e| e.to_string()))
+        .and_then(|json| std::fs::write(&tmp_path, json).map_err(|e| e.to_string()))
+        .and_then(|_| std::fs::rename(&tmp_path, &path).map_err(|e| e.to_string()));
+
+    index.last_saved = Some(Instant::now());
+    match result {
+        Ok(()) => index.dirty = false,
+        Err(e) => eprintln!("Error while saving perceptual hashes: {}", e),
+    }
+}
+
+fn load_index() -> PerceptualIndex {
+    std::fs::read_to_string(index_path())
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn index_path() -> PathBuf {
+    paths::cache_dir().join("perceptual_hashes.json")
+}
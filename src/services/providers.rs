@@ -0,0 +1,278 @@
+use serde::Deserialize;
+
+use crate::models::{ImageRecord, ProviderKind, WaifuImageResponse, WaifuTags};
+use crate::services::error::ServiceError;
+use crate::services::runtime::http_client;
+
+/// A source of waifu images.
+///
+/// Each backend knows which tags it offers and how to turn a tag into one or
+/// more image URLs. `ProviderKind` implements this trait by dispatching to the
+/// matching backend, so callers can simply use `settings.provider`.
+pub trait ImageProvider {
+    /// Tags bundled with the application, used when the live list is unavailable.
+    fn fallback_tags(&self) -> WaifuTags;
+
+    async fn list_tags(&self) -> Result<WaifuTags, ServiceError>;
+
+    async fn fetch_random(&self, tag: &str, is_nsfw: bool) -> Result<ImageRecord, ServiceError>;
+
+    /// Up to `count` images of `tag`. Backends that support it are asked to
+    /// leave out the URLs in `exclude`; the others may still return them.
+    async fn fetch_many(
+        &self,
+        tag: &str,
+        is_nsfw: bool,
+        count: usize,
+        exclude: &[String],
+    ) -> Result<Vec<ImageRecord>, ServiceError>;
+}
+
+pub struct WaifuPics;
+
+pub struct WaifuIm;
+
+#[derive(Deserialize)]
+struct WaifuPicsEndpoints {
+    sfw: Vec<String>,
+    nsfw: Vec<String>,
+}
+
+impl ImageProvider for WaifuPics {
+    fn fallback_tags(&self) -> WaifuTags {
+        WaifuTags {
+            versatile: vec![
+                "waifu".to_string(),
+                "neko".to_string(),
+                "shinobu".to_string(),
+                "megumin".to_string(),
+                "bully".to_string(),
+                "cuddle".to_string(),
+                "cry".to_string(),
+                "hug".to_string(),
+                "awoo".to_string(),
+                "kiss".to_string(),
+                "lick".to_string(),
+                "pat".to_string(),
+                "smug".to_string(),
+                "bonk".to_string(),
+                "yeet".to_string(),
+                "blush".to_string(),
+                "smile".to_string(),
+                "wave".to_string(),
+                "highfive".to_string(),
+                "handhold".to_string(),
+                "nom".to_string(),
+                "bite".to_string(),
+                "glomp".to_string(),
+                "slap".to_string(),
+                "kill".to_string(),
+                "kick".to_string(),
+                "happy".to_string(),
+                "wink".to_string(),
+                "poke".to_string(),
+                "dance".to_string(),
+                "cringe".to_string(),
+            ],
+            nsfw: vec![
+                "waifu".to_string(),
+                "neko".to_string(),
+                "trap".to_string(),
+                "blowjob".to_string(),
+            ],
+        }
+    }
+
+    async fn list_tags(&self) -> Result<WaifuTags, ServiceError> {
+        let url = "https://api.waifu.pics/endpoints";
+
+        eprintln!("Request URL: {}", url);
+
+        let response = http_client().get(url).send().await?;
+        let response = ServiceError::check_response(response)?;
+
+        let endpoints = response.json::<WaifuPicsEndpoints>().await?;
+
+        Ok(WaifuTags {
+            versatile: endpoints.sfw,
+            nsfw: endpoints.nsfw,
+        })
+    }
+
+    async fn fetch_random(&self, tag: &str, is_nsfw: bool) -> Result<ImageRecord, ServiceError> {
+        let url = format!("https://api.waifu.pics/{}/{}", waifu_pics_type(is_nsfw), tag);
+
+        eprintln!("Request URL: {}", url);
+
+        let response = http_client().get(&url).send().await?;
+        let response = ServiceError::check_response(response)?;
+
+        let json = response.json::<serde_json::Value>().await?;
+
+        json.get("url")
+            .and_then(|u| u.as_str())
+            .map(|u| ImageRecord::new(u.to_string(), tag, is_nsfw, ProviderKind::WaifuPics))
+            .ok_or_else(|| ServiceError::Parse("No image URL found in response".to_string()))
+    }
+
+    async fn fetch_many(
+        &self,
+        tag: &str,
+        is_nsfw: bool,
+        count: usize,
+        exclude: &[String],
+    ) -> Result<Vec<ImageRecord>, ServiceError> {
+        let url = format!("https://api.waifu.pics/many/{}/{}", waifu_pics_type(is_nsfw), tag);
+
+        eprintln!("Request URL: {}", url);
+
+        let response = http_client()
+            .post(&url)
+            .json(&serde_json::json!({ "exclude": exclude }))
+            .send()
+            .await?;
+        let response = ServiceError::check_response(response)?;
+
+        let json = response.json::<serde_json::Value>().await?;
+
+        let files = json
+            .get("files")
+            .and_then(|f| f.as_array())
+            .ok_or_else(|| ServiceError::Parse("No image URLs found in response".to_string()))?;
+
+        Ok(files
+            .iter()
+            .filter_map(|f| f.as_str())
+            .map(|u| ImageRecord::new(u.to_string(), tag, is_nsfw, ProviderKind::WaifuPics))
+            .take(count)
+            .collect())
+    }
+}
+
+impl ImageProvider for WaifuIm {
+    fn fallback_tags(&self) -> WaifuTags {
+        WaifuTags {
+            versatile: vec![
+                "waifu".to_string(),
+                "maid".to_string(),
+                "marin-kitagawa".to_string(),
+                "mori-calliope".to_string(),
+                "raiden-shogun".to_string(),
+                "oppai".to_string(),
+                "selfies".to_string(),
+                "uniform".to_string(),
+                "kamisato-ayaka".to_string(),
+            ],
+            nsfw: vec![
+                "ass".to_string(),
+                "hentai".to_string(),
+                "milf".to_string(),
+                "oral".to_string(),
+                "paizuri".to_string(),
+                "ecchi".to_string(),
+                "ero".to_string(),
+            ],
+        }
+    }
+
+    async fn list_tags(&self) -> Result<WaifuTags, ServiceError> {
+        let url = "https://api.waifu.im/tags";
+
+        eprintln!("Request URL: {}", url);
+
+        let response = http_client()
+            .get(url)
+            .header("Accept-Version", "v5")
+            .send()
+            .await?;
+        let response = ServiceError::check_response(response)?;
+
+        Ok(response.json::<WaifuTags>().await?)
+    }
+
+    async fn fetch_random(&self, tag: &str, is_nsfw: bool) -> Result<ImageRecord, ServiceError> {
+        let response = waifu_im_search(tag, is_nsfw, false).await?;
+
+        response
+            .images
+            .into_iter()
+            .next()
+            .map(|image| ImageRecord::with_details(tag, ProviderKind::WaifuIm, image))
+            .ok_or_else(|| ServiceError::Parse("No image found in response".to_string()))
+    }
+
+    async fn fetch_many(
+        &self,
+        tag: &str,
+        is_nsfw: bool,
+        count: usize,
+        _exclude: &[String],
+    ) -> Result<Vec<ImageRecord>, ServiceError> {
+        let response = waifu_im_search(tag, is_nsfw, count > 1).await?;
+
+        Ok(response
+            .images
+            .into_iter()
+            .map(|image| ImageRecord::with_details(tag, ProviderKind::WaifuIm, image))
+            .take(count)
+            .collect())
+    }
+}
+
+impl ImageProvider for ProviderKind {
+    fn fallback_tags(&self) -> WaifuTags {
+        match self {
+            ProviderKind::WaifuPics => WaifuPics.fallback_tags(),
+            ProviderKind::WaifuIm => WaifuIm.fallback_tags(),
+        }
+    }
+
+    async fn list_tags(&self) -> Result<WaifuTags, ServiceError> {
+        match self {
+            ProviderKind::WaifuPics => WaifuPics.list_tags().await,
+            ProviderKind::WaifuIm => WaifuIm.list_tags().await,
+        }
+    }
+
+    async fn fetch_random(&self, tag: &str, is_nsfw: bool) -> Result<ImageRecord, ServiceError> {
+        match self {
+            ProviderKind::WaifuPics => WaifuPics.fetch_random(tag, is_nsfw).await,
+            ProviderKind::WaifuIm => WaifuIm.fetch_random(tag, is_nsfw).await,
+        }
+    }
+
+    async fn fetch_many(
+        &self,
+        tag: &str,
+        is_nsfw: bool,
+        count: usize,
+        exclude: &[String],
+    ) -> Result<Vec<ImageRecord>, ServiceError> {
+        match self {
+            ProviderKind::WaifuPics => WaifuPics.fetch_many(tag, is_nsfw, count, exclude).await,
+            ProviderKind::WaifuIm => WaifuIm.fetch_many(tag, is_nsfw, count, exclude).await,
+        }
+    }
+}
+
+fn waifu_pics_type(is_nsfw: bool) -> &'static str {
+    if is_nsfw { "nsfw" } else { "sfw" }
+}
+
+async fn waifu_im_search(tag: &str, is_nsfw: bool, many: bool) -> Result<WaifuImageResponse, ServiceError> {
+    let url = "https://api.waifu.im/search";
+    let is_nsfw = is_nsfw.to_string();
+    let many = many.to_string();
+
+    eprintln!("Request URL: {}?included_tags={}&is_nsfw={}&many={}", url, tag, is_nsfw, many);
+
+    let response = http_client()
+        .get(url)
+        .header("Accept-Version", "v5")
+        .query(&[("included_tags", tag), ("is_nsfw", &is_nsfw), ("many", &many)])
+        .send()
+        .await?;
+    let response = ServiceError::check_response(response)?;
+
+    Ok(response.json::<WaifuImageResponse>().await?)
+}
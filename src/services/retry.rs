@@ -0,0 +1,163 @@
+use rand::Rng;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::models::RetryPolicy;
+use crate::services::error::ServiceError;
+
+static API_RATE_LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+
+/// The limiter shared by every API request in the process (user actions,
+/// prefetching, slideshow...), so together they never exceed the configured rate.
+pub fn api_rate_limiter() -> &'static RateLimiter {
+    API_RATE_LIMITER.get_or_init(|| RateLimiter::new(60))
+}
+
+/// Token bucket allowing `per_minute` requests per minute, with bursts of up to
+/// a tenth of that. A `Retry-After` from the server pauses every caller.
+pub struct RateLimiter {
+    state: Mutex<RateLimiterState>,
+}
+
+struct RateLimiterState {
+    per_minute: u32,
+    tokens: f64,
+    last_refill: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        let per_minute = per_minute.max(1);
+        Self {
+            state: Mutex::new(RateLimiterState {
+                per_minute,
+                tokens: burst_size(per_minute),
+                last_refill: Instant::now(),
+                paused_until: None,
+            }),
+        }
+    }
+
+    pub async fn set_rate(&self, per_minute: u32) {
+        let mut state = self.state.lock().await;
+        state.per_minute = per_minute.max(1);
+        state.tokens = state.tokens.min(burst_size(state.per_minute));
+    }
+
+    /// Stops handing out permits for `delay`.
+    pub async fn pause_for(&self, delay: Duration) {
+        let mut state = self.state.lock().await;
+        let until = Instant::now() + delay;
+        if state.paused_until.is_none_or(|current| current < until) {
+            state.paused_until = Some(until);
+        }
+    }
+
+    /// Waits until a request may be sent.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().await;
+                let now = Instant::now();
+
+                if let Some(until) = state.paused_until {
+                    if until > now {
+                        until - now
+                    } else {
+                        state.paused_until = None;
+                        continue;
+                    }
+                } else {
+                    let per_second = state.per_minute as f64 / 60.0;
+                    let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+                    state.tokens = (state.tokens + elapsed * per_second).min(burst_size(state.per_minute));
+                    state.last_refill = now;
+
+                    if state.tokens >= 1.0 {
+                        state.tokens -= 1.0;
+                        return;
+                    }
+
+                    Duration::from_secs_f64((1.0 - state.tokens) / per_second)
+                }
+            };
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+fn burst_size(per_minute: u32) -> f64 {
+    (per_minute as f64 / 10.0).max(1.0)
+}
+
+/// Delay before attempt number `attempt + 1`, given that `attempt` attempts failed.
+pub fn backoff_delay(policy: &RetryPolicy, attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16);
+    let delay_ms = policy
+        .initial_backoff_ms
+        .saturating_mul(1 << exponent)
+        .min(policy.max_backoff_ms);
+
+    let delay_ms = if policy.jitter && delay_ms > 0 {
+        rand::thread_rng().gen_range(delay_ms / 2..=delay_ms)
+    } else {
+        delay_ms
+    };
+
+    Duration::from_millis(delay_ms)
+}
+
+/// Runs `operation` until it succeeds, fails with a non-retryable error, or
+/// `policy.max_attempts` is reached. A `Retry-After` sent by the server takes
+/// precedence over the computed backoff, unless it is longer than
+/// `policy.max_backoff_ms`: the error is then returned right away rather than
+/// leaving this caller waiting. Either way `limiter`, the one guarding the
+/// server that answered, is paused for that long so other callers hold off too.
+pub async fn with_retry<T, F, Fut>(
+    policy: &RetryPolicy,
+    limiter: Option<&RateLimiter>,
+    mut operation: F,
+) -> Result<T, ServiceError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ServiceError>>,
+{
+    let mut attempt = 1;
+    loop {
+        let result = operation().await;
+        if let (Err(ServiceError::RateLimited { retry_after: Some(retry_after) }), Some(limiter)) = (&result, limiter) {
+            limiter.pause_for(*retry_after).await;
+        }
+
+        match result {
+            Ok(value) => return Ok(value),
+            Err(ServiceError::RateLimited { retry_after: Some(retry_after) })
+                if retry_after > Duration::from_millis(policy.max_backoff_ms) =>
+            {
+                eprintln!("Server asked to wait {:.0}s, giving up", retry_after.as_secs_f64());
+                return Err(ServiceError::RateLimited { retry_after: Some(retry_after) });
+            }
+            Err(e) if e.is_retryable() && attempt < policy.max_attempts => {
+                let delay = match &e {
+                    ServiceError::RateLimited { retry_after: Some(retry_after) } => *retry_after,
+                    _ => backoff_delay(policy, attempt),
+                };
+                eprintln!(
+                    "{} - retrying in {:.1}s (attempt {}/{})",
+                    e,
+                    delay.as_secs_f64(),
+                    attempt + 1,
+                    policy.max_attempts
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tokio::task::JoinHandle;
+
+use crate::services::error::ServiceError;
+
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// The application-wide tokio runtime. Network and disk work runs on its worker
+/// threads so the GTK main loop never blocks.
+pub fn runtime() -> &'static Runtime {
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("waifu-worker")
+            .enable_all()
+            .build()
+            .expect("Failed to start the async runtime")
+    })
+}
+
+/// Shared HTTP client so connections to the API and image hosts are pooled.
+pub fn http_client() -> &'static reqwest::Client {
+    HTTP_CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .user_agent(concat!("waifu-generator/", env!("CARGO_PKG_VERSION")))
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(60))
+            .pool_idle_timeout(Duration::from_secs(90))
+            .build()
+            .expect("Failed to build the HTTP client")
+    })
+}
+
+/// Runs `future` on the shared runtime.
+///
+/// The returned handle can be awaited from `glib::spawn_future_local`, which
+/// brings the result back to the GTK main loop.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    runtime().spawn(future)
+}
+
+/// Runs blocking work (file I/O, image decoding) on the runtime's blocking pool.
+pub fn spawn_blocking<F, R>(work: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    runtime().spawn_blocking(work)
+}
+
+/// Runs a fallible `future` on the shared runtime and awaits its result,
+/// folding a panicked or cancelled task into the error.
+pub async fn run<F, T>(future: F) -> Result<T, ServiceError>
+where
+    F: Future<Output = Result<T, ServiceError>> + Send + 'static,
+    T: Send + 'static,
+{
+    spawn(future).await?
+}
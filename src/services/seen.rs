@@ -0,0 +1,82 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::services::history::HistoryEntry;
+use crate::services::perceptual;
+
+/// Images that were already shown, by URL and by content hash, so a new pick
+/// can be rolled again when it repeats one. Built from the history log, which
+/// records the content hash of every image it can.
+#[derive(Debug, Clone, Default)]
+pub struct SeenImages {
+    /// Content hash of each seen URL, when it was recorded.
+    urls: HashMap<String, Option<String>>,
+    hashes: HashSet<String>,
+    /// URLs in the order they were seen, for the provider's exclude list.
+    order: Vec<String>,
+}
+
+impl SeenImages {
+    pub fn from_history(entries: &[HistoryEntry]) -> Self {
+        let mut seen = Self::default();
+        for entry in entries {
+            seen.insert(&entry.image.url, entry.content_hash.as_deref());
+        }
+        seen
+    }
+
+    pub fn insert(&mut self, url: &str, content_hash: Option<&str>) {
+        let known = self.urls.entry(url.to_string()).or_insert_with(|| {
+            self.order.push(url.to_string());
+            None
+        });
+        if let Some(content_hash) = content_hash {
+            *known = Some(content_hash.to_string());
+            self.hashes.insert(content_hash.to_string());
+        }
+    }
+
+    /// Adds the images of `other`, which were seen before the ones in `self`.
+    pub fn merge(&mut self, other: SeenImages) {
+        let mut newer = std::mem::replace(self, other);
+        for url in newer.order {
+            let content_hash = newer.urls.remove(&url).flatten();
+            self.insert(&url, content_hash.as_deref());
+        }
+    }
+
+    pub fn contains_url(&self, url: &str) -> bool {
+        self.urls.contains_key(url)
+    }
+
+    pub fn contains_hash(&self, content_hash: &str) -> bool {
+        self.hashes.contains(content_hash)
+    }
+
+    /// Whether a seen image looks like the one with perceptual hash `hash`.
+    /// Only images whose perceptual hash was computed are compared.
+    pub fn contains_similar(&self, hash: u64) -> bool {
+        perceptual::is_similar_to_any(hash, &self.hashes)
+    }
+
+    /// Seen images whose perceptual hash is not known, as their URL and
+    /// content hash if it was recorded, to be hashed with
+    /// `perceptual::hash_cached`.
+    pub fn without_perceptual_hash(&self) -> Vec<(String, Option<String>)> {
+        self.order
+            .iter()
+            .filter_map(|url| {
+                let content_hash = self.urls.get(url)?;
+                match content_hash {
+                    Some(content_hash) if perceptual::lookup(content_hash).is_some() => None,
+                    _ => Some((url.clone(), content_hash.clone())),
+                }
+            })
+            .collect()
+    }
+
+    /// Up to `limit` of the most recently seen URLs.
+    pub fn recent_urls(&self, limit: usize) -> Vec<String> {
+        let start = self.order.len().saturating_sub(limit);
+        self.order[start..].to_vec()
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::models::{ImageRecord, UserSettings};
+use crate::services::disk_cache::{self, DiskCache};
+use crate::services::favorites::FavoritesStore;
+use crate::services::history::HistoryLog;
+use crate::services::perceptual;
+
+/// Where a copy of an image was found.
+#[derive(Debug, Clone)]
+pub enum ImageLocation {
+    History(ImageRecord),
+    Favorite(ImageRecord),
+    Saved(PathBuf),
+}
+
+impl ImageLocation {
+    pub fn display_name(&self) -> String {
+        match self {
+            ImageLocation::History(_) => "History".to_string(),
+            ImageLocation::Favorite(_) => "Favorite".to_string(),
+            ImageLocation::Saved(path) => format!(
+                "Saved as {}",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ),
+        }
+    }
+}
+
+/// One distinct file content, with every place it was found.
+#[derive(Debug, Clone)]
+pub struct SimilarImage {
+    /// A copy that can be read to show the image.
+    pub path: PathBuf,
+    pub locations: Vec<ImageLocation>,
+}
+
+impl SimilarImage {
+    /// The record to show the image in the main window, unless it only exists
+    /// as a saved file.
+    pub fn record(&self) -> Option<&ImageRecord> {
+        self.locations.iter().find_map(|location| match location {
+            ImageLocation::History(image) | ImageLocation::Favorite(image) => Some(image),
+            ImageLocation::Saved(_) => None,
+        })
+    }
+}
+
+/// Groups of images that look alike across the history (as far as it is still
+/// in the image cache), the favorites and the files in `saved_dirs`. Identical
+/// files count as one image, so a group always holds different files. Reads
+/// every file, so it must run off the main thread.
+pub fn find_similar(settings: &UserSettings, saved_dirs: &[PathBuf]) -> Vec<Vec<SimilarImage>> {
+    let mut scan = Scan::default();
+
+    let mut history: HashMap<String, ImageRecord> = HashMap::new();
+    let mut history_urls = Vec::new();
+    // Hashes of seen images are kept even once they left the cache, since
+    // skipping similar images compares against them.
+    let mut referenced: HashSet<String> = HashSet::new();
+    for entry in HistoryLog::load().into_iter().rev() {
+        referenced.extend(entry.content_hash);
+        if !history.contains_key(&entry.image.url) {
+            history_urls.push(entry.image.url.clone());
+            history.insert(entry.image.url.clone(), entry.image);
+        }
+    }
+    DiskCache::for_settings(settings).peek_each(history_urls.iter().map(String::as_str), |url, path, bytes| {
+        if let Some(image) = history.remove(url) {
+            scan.add(path, &bytes, ImageLocation::History(image));
+        }
+    });
+
+    for favorite in FavoritesStore::load().favorites {
+        let path = favorite.local_path();
+        if let Ok(bytes) = std::fs::read(&path) {
+            scan.add(path, &bytes, ImageLocation::Favorite(favorite.image));
+        }
+    }
+
+    let mut scanned_dirs = HashSet::new();
+    for dir in saved_dirs.iter().filter(|dir| scanned_dirs.insert(*dir)) {
+        for path in saved_files(dir) {
+            if let Ok(bytes) = std::fs::read(&path) {
+                scan.add(path.clone(), &bytes, ImageLocation::Saved(path));
+            }
+        }
+    }
+
+    referenced.extend(scan.by_content.keys().cloned());
+    perceptual::retain(&referenced);
+    perceptual::flush_now();
+    eprintln!("Compared {} distinct images", scan.images.len());
+
+    let hashes: Vec<u64> = scan.images.iter().map(|(_, hash)| *hash).collect();
+    let mut images: Vec<Option<SimilarImage>> = scan.images.into_iter().map(|(image, _)| Some(image)).collect();
+    perceptual::cluster(&hashes)
+        .into_iter()
+        .map(|group| group.into_iter().filter_map(|i| images[i].take()).collect())
+        .collect()
+}
+
+/// Distinct contents seen so far, with their perceptual hash.
+#[derive(Default)]
+struct Scan {
+    images: Vec<(SimilarImage, u64)>,
+    by_content: HashMap<String, usize>,
+}
+
+impl Scan {
+    fn add(&mut self, path: PathBuf, bytes: &[u8], location: ImageLocation) {
+        let content_hash = disk_cache::content_hash(bytes);
+        if let Some(&position) = self.by_content.get(&content_hash) {
+            self.images[position].0.locations.push(location);
+            return;
+        }
+
+        let Some(hash) = perceptual::hash_image(&content_hash, bytes) else {
+            return;
+        };
+        self.by_content.insert(content_hash, self.images.len());
+        self.images.push((SimilarImage { path, locations: vec![location] }, hash));
+    }
+}
+
+/// Files directly inside `dir`, which is not searched recursively.
+fn saved_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+    files
+}
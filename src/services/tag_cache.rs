@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::models::{ProviderKind, WaifuTags};
+use crate::paths;
+
+/// How long a downloaded tag list is trusted before asking the provider again.
+pub const TAG_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedTags {
+    pub fetched_at: u64,
+    pub tags: WaifuTags,
+}
+
+impl CachedTags {
+    pub fn new(tags: WaifuTags) -> Self {
+        Self {
+            fetched_at: now_secs(),
+            tags,
+        }
+    }
+
+    pub fn is_fresh(&self) -> bool {
+        now_secs().saturating_sub(self.fetched_at) < TAG_CACHE_TTL.as_secs()
+    }
+}
+
+pub fn cache_path(provider: ProviderKind) -> PathBuf {
+    let file_name = match provider {
+        ProviderKind::WaifuPics => "tags-waifu-pics.json",
+        ProviderKind::WaifuIm => "tags-waifu-im.json",
+    };
+
+    paths::cache_dir().join(file_name)
+}
+
+pub fn load(provider: ProviderKind) -> Option<CachedTags> {
+    let json = std::fs::read_to_string(cache_path(provider)).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+pub fn store(provider: ProviderKind, tags: &CachedTags) -> Result<(), Box<dyn std::error::Error>> {
+    let path = cache_path(provider);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(tags)?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
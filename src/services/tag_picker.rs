@@ -0,0 +1,197 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::sync::Mutex;
+
+use crate::models::{TagStrategy, UserSettings};
+use crate::services::error::ServiceError;
+
+/// A selected category and whether it is an NSFW one.
+type Tag = (String, bool);
+
+/// Where the round robin and the shuffle bag are, shared by every caller in the
+/// process so that the main window, prefetching and the slideshow take turns.
+static STATE: Mutex<PickerState> = Mutex::new(PickerState::new());
+
+struct PickerState {
+    next: usize,
+    bag: Vec<Tag>,
+    /// The categories and weights the bag was filled from, to refill it as
+    /// soon as the selection changes.
+    bag_source: Vec<(Tag, u32)>,
+}
+
+impl PickerState {
+    const fn new() -> Self {
+        Self { next: 0, bag: Vec::new(), bag_source: Vec::new() }
+    }
+}
+
+/// Chooses the category of the next image according to
+/// `UserSettings::tag_strategy`.
+pub fn pick_tag(settings: &UserSettings) -> Result<Tag, ServiceError> {
+    let (tag, is_nsfw) = {
+        let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+        pick_with(settings, &mut state, &mut rand::thread_rng())?
+    };
+
+    eprintln!("Provider: {}", settings.provider.display_name());
+    eprintln!("Selected tag ({}): {}", settings.tag_strategy.display_name(), tag);
+    eprintln!("Type: {}", if is_nsfw { "NSFW" } else { "SFW" });
+
+    Ok((tag, is_nsfw))
+}
+
+/// `pick_tag` over an explicit picker state and source of randomness.
+fn pick_with(settings: &UserSettings, state: &mut PickerState, rng: &mut impl Rng) -> Result<Tag, ServiceError> {
+    let tags = selected_tags(settings);
+    if tags.is_empty() {
+        return Err(ServiceError::EmptySelection);
+    }
+
+    Ok(match settings.tag_strategy {
+        TagStrategy::Weighted => pick_weighted(settings, rng),
+        TagStrategy::Uniform => tags.choose(rng).cloned().unwrap(),
+        TagStrategy::RoundRobin => {
+            let position = state.next % tags.len();
+            state.next = position + 1;
+            tags[position].clone()
+        }
+        TagStrategy::ShuffleBag => draw_from_bag(settings, &tags, state, rng),
+    })
+}
+
+/// Every selected category, SFW ones first.
+fn selected_tags(settings: &UserSettings) -> Vec<Tag> {
+    let versatile = settings.selected_versatile.iter().map(|tag| (tag.clone(), false));
+    let nsfw = settings.selected_nsfw.iter().map(|tag| (tag.clone(), true));
+    versatile.chain(nsfw).collect()
+}
+
+fn pick_weighted(settings: &UserSettings, rng: &mut impl Rng) -> Tag {
+    let is_nsfw = match (settings.selected_versatile.is_empty(), settings.selected_nsfw.is_empty()) {
+        (false, false) => rng.gen_bool(settings.nsfw_ratio.clamp(0.0, 1.0)),
+        (versatile_empty, _) => versatile_empty,
+    };
+
+    let side = if is_nsfw { &settings.selected_nsfw } else { &settings.selected_versatile };
+    let tag = side
+        .choose_weighted(rng, |tag| settings.tag_weights.get(tag, is_nsfw))
+        .unwrap();
+    (tag.clone(), is_nsfw)
+}
+
+fn draw_from_bag(settings: &UserSettings, tags: &[Tag], state: &mut PickerState, rng: &mut impl Rng) -> Tag {
+    let source: Vec<(Tag, u32)> = tags
+        .iter()
+        .map(|tag| (tag.clone(), settings.tag_weights.get(&tag.0, tag.1)))
+        .collect();
+
+    if state.bag.is_empty() || state.bag_source != source {
+        let mut bag: Vec<Tag> = source
+            .iter()
+            .flat_map(|(tag, weight)| std::iter::repeat_n(tag.clone(), *weight as usize))
+            .collect();
+        bag.shuffle(rng);
+        state.bag = bag;
+        state.bag_source = source;
+    }
+    state.bag.pop().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn settings(strategy: TagStrategy, versatile: &[&str], nsfw: &[&str]) -> UserSettings {
+        UserSettings {
+            tag_strategy: strategy,
+            selected_versatile: versatile.iter().map(|tag| tag.to_string()).collect(),
+            selected_nsfw: nsfw.iter().map(|tag| tag.to_string()).collect(),
+            ..UserSettings::default()
+        }
+    }
+
+    fn draw(settings: &UserSettings, state: &mut PickerState, rng: &mut StdRng, count: usize) -> Vec<Tag> {
+        (0..count).map(|_| pick_with(settings, state, rng).unwrap()).collect()
+    }
+
+    fn tag(name: &str, is_nsfw: bool) -> Tag {
+        (name.to_string(), is_nsfw)
+    }
+
+    #[test]
+    fn empty_selection_is_an_error() {
+        let settings = settings(TagStrategy::Weighted, &[], &[]);
+        let result = pick_with(&settings, &mut PickerState::new(), &mut StdRng::seed_from_u64(1));
+        assert!(matches!(result, Err(ServiceError::EmptySelection)));
+    }
+
+    #[test]
+    fn nsfw_share_follows_the_ratio() {
+        let mut settings = settings(TagStrategy::Weighted, &["waifu"], &["trap"]);
+        let mut rng = StdRng::seed_from_u64(7);
+
+        settings.nsfw_ratio = 0.25;
+        let picks = draw(&settings, &mut PickerState::new(), &mut rng, 10_000);
+        let nsfw = picks.iter().filter(|(_, is_nsfw)| *is_nsfw).count();
+        assert!((2_200..=2_800).contains(&nsfw), "{} NSFW picks", nsfw);
+
+        settings.nsfw_ratio = 0.0;
+        assert!(draw(&settings, &mut PickerState::new(), &mut rng, 100).iter().all(|(_, is_nsfw)| !is_nsfw));
+
+        settings.nsfw_ratio = 1.0;
+        assert!(draw(&settings, &mut PickerState::new(), &mut rng, 100).iter().all(|(_, is_nsfw)| *is_nsfw));
+    }
+
+    #[test]
+    fn ratio_is_ignored_when_one_side_is_empty() {
+        let mut settings = settings(TagStrategy::Weighted, &[], &["trap"]);
+        settings.nsfw_ratio = 0.0;
+        let picks = draw(&settings, &mut PickerState::new(), &mut StdRng::seed_from_u64(3), 20);
+        assert!(picks.iter().all(|pick| *pick == tag("trap", true)));
+    }
+
+    #[test]
+    fn weights_bias_the_choice() {
+        let mut settings = settings(TagStrategy::Weighted, &["waifu", "neko"], &[]);
+        settings.tag_weights.set("waifu", false, 3);
+        let picks = draw(&settings, &mut PickerState::new(), &mut StdRng::seed_from_u64(11), 10_000);
+        let waifu = picks.iter().filter(|(name, _)| name == "waifu").count();
+        assert!((7_200..=7_800).contains(&waifu), "{} waifu picks", waifu);
+    }
+
+    #[test]
+    fn round_robin_takes_turns() {
+        let settings = settings(TagStrategy::RoundRobin, &["waifu", "neko"], &["trap"]);
+        let picks = draw(&settings, &mut PickerState::new(), &mut StdRng::seed_from_u64(5), 4);
+        assert_eq!(picks, vec![tag("waifu", false), tag("neko", false), tag("trap", true), tag("waifu", false)]);
+    }
+
+    #[test]
+    fn shuffle_bag_draws_each_tag_by_its_weight() {
+        let mut settings = settings(TagStrategy::ShuffleBag, &["waifu", "neko"], &[]);
+        settings.tag_weights.set("waifu", false, 2);
+        let mut picks = draw(&settings, &mut PickerState::new(), &mut StdRng::seed_from_u64(9), 3);
+        picks.sort();
+        assert_eq!(picks, vec![tag("neko", false), tag("waifu", false), tag("waifu", false)]);
+    }
+
+    #[test]
+    fn shuffle_bag_is_refilled_when_the_selection_changes() {
+        let mut state = PickerState::new();
+        let mut rng = StdRng::seed_from_u64(13);
+        let mut settings = settings(TagStrategy::ShuffleBag, &["waifu", "neko"], &[]);
+        draw(&settings, &mut state, &mut rng, 1);
+        assert_eq!(state.bag.len(), 1);
+
+        settings.selected_versatile = vec!["shinobu".to_string()];
+        assert_eq!(draw(&settings, &mut state, &mut rng, 1), vec![tag("shinobu", false)]);
+        assert!(state.bag.is_empty());
+
+        settings.tag_weights.set("shinobu", false, 4);
+        assert_eq!(draw(&settings, &mut state, &mut rng, 1), vec![tag("shinobu", false)]);
+        assert_eq!(state.bag.len(), 3);
+    }
+}
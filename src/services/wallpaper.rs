@@ -0,0 +1,182 @@
+use gtk4::gio;
+use gtk4::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::models::{UserSettings, WallpaperBackend};
+use crate::paths;
+use crate::services::error::ServiceError;
+
+/// Where the current wallpaper is kept. Each one gets a new file name, since
+/// some desktops ignore a path they already show, and the previous ones are
+/// removed once it is applied so they do not pile up on disk.
+fn wallpaper_dir() -> PathBuf {
+    paths::data_dir().join("wallpaper")
+}
+
+impl WallpaperBackend {
+    /// Guesses the backend from the running desktop session.
+    pub fn detect() -> Option<WallpaperBackend> {
+        let desktop = std::env::var("XDG_CURRENT_DESKTOP")
+            .unwrap_or_default()
+            .to_lowercase();
+
+        if desktop.contains("gnome") || desktop.contains("unity") || desktop.contains("budgie") {
+            return Some(WallpaperBackend::Gnome);
+        }
+        if desktop.contains("kde") {
+            return Some(WallpaperBackend::Kde);
+        }
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            if command_exists("swww") {
+                return Some(WallpaperBackend::Swww);
+            }
+            if command_exists("swaybg") {
+                return Some(WallpaperBackend::Swaybg);
+            }
+        }
+        if std::env::var_os("DISPLAY").is_some() && command_exists("feh") {
+            return Some(WallpaperBackend::Feh);
+        }
+        None
+    }
+
+    /// Sets the image at `path` as the desktop background. Blocks until the
+    /// backend has applied it.
+    pub fn apply(&self, path: &Path) -> Result<(), ServiceError> {
+        let path_str = path.to_string_lossy();
+        match self {
+            WallpaperBackend::Gnome => {
+                let uri = gio::File::for_path(path).uri();
+                run_command("gsettings", &["set", "org.gnome.desktop.background", "picture-uri", &uri])?;
+                run_command("gsettings", &["set", "org.gnome.desktop.background", "picture-uri-dark", &uri])
+            }
+            WallpaperBackend::Kde => run_command("plasma-apply-wallpaperimage", &[&path_str]),
+            WallpaperBackend::Swww => run_command("swww", &["img", &path_str]),
+            WallpaperBackend::Swaybg => {
+                // swaybg keeps running to draw the background, so replace the
+                // instance we started last and leave the new one running.
+                stop_own_swaybg();
+                let mut child = Command::new("swaybg")
+                    .args(["-m", "fill", "-i", &path_str])
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .map_err(|e| command_error("swaybg", e))?;
+
+                let pid_file = swaybg_pid_file();
+                if let Some(parent) = pid_file.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&pid_file, child.id().to_string())?;
+                // Reap it once it is replaced, so it does not linger as a zombie.
+                std::thread::spawn(move || child.wait());
+                Ok(())
+            }
+            WallpaperBackend::Feh => run_command("feh", &["--bg-fill", &path_str]),
+        }
+    }
+}
+
+/// Copies `image_file` to the wallpaper directory and applies it with the
+/// backend from the settings, or the detected one. Returns the copy.
+pub fn set_wallpaper(image_file: &Path, settings: &UserSettings) -> Result<PathBuf, ServiceError> {
+    let backend = settings
+        .wallpaper_backend
+        .or_else(WallpaperBackend::detect)
+        .ok_or_else(|| ServiceError::Wallpaper("No supported desktop detected".to_string()))?;
+
+    let extension = image_file
+        .extension()
+        .map(|ext| ext.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "png".to_string());
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let dir = wallpaper_dir();
+    std::fs::create_dir_all(&dir)?;
+    let target = dir.join(format!("wallpaper-{}.{}", stamp, extension));
+    std::fs::copy(image_file, &target)?;
+
+    eprintln!("Setting wallpaper with {}: {}", backend.display_name(), target.display());
+    if let Err(e) = backend.apply(&target) {
+        let _ = std::fs::remove_file(&target);
+        return Err(e);
+    }
+    remove_previous_wallpapers(&dir, &target);
+    Ok(target)
+}
+
+/// Deletes every file in `dir` but `current`, now that it is no longer shown.
+fn remove_previous_wallpapers(dir: &Path, current: &Path) {
+    let Ok(files) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for path in files.filter_map(|file| file.ok()).map(|file| file.path()) {
+        if path != current && path.is_file() {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
+
+/// Records the swaybg started by `WallpaperBackend::apply`, so that only that
+/// one is replaced and not instances for other outputs or from the compositor.
+fn swaybg_pid_file() -> PathBuf {
+    paths::state_dir().join("swaybg.pid")
+}
+
+/// Stops the swaybg recorded in the pid file, if it is still running. The
+/// process name is checked first in case the pid was reused since.
+fn stop_own_swaybg() {
+    let pid_file = swaybg_pid_file();
+    let Some(pid) = std::fs::read_to_string(&pid_file)
+        .ok()
+        .and_then(|pid| pid.trim().parse::<u32>().ok())
+    else {
+        return;
+    };
+    let _ = std::fs::remove_file(&pid_file);
+
+    let is_swaybg = std::fs::read_to_string(format!("/proc/{}/comm", pid))
+        .is_ok_and(|name| name.trim() == "swaybg");
+    if is_swaybg {
+        let _ = Command::new("kill").arg(pid.to_string()).status();
+    }
+}
+
+fn run_command(program: &str, args: &[&str]) -> Result<(), ServiceError> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| command_error(program, e))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(ServiceError::Wallpaper(format!(
+            "{} failed ({}): {}",
+            program,
+            output.status,
+            stderr.trim()
+        )))
+    }
+}
+
+fn command_error(program: &str, e: std::io::Error) -> ServiceError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        ServiceError::Wallpaper(format!("{} is not installed", program))
+    } else {
+        ServiceError::Wallpaper(format!("Could not run {}: {}", program, e))
+    }
+}
+
+fn command_exists(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
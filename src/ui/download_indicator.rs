@@ -0,0 +1,166 @@
+use gtk4::prelude::*;
+use gtk4::{cairo, Box as GtkBox, Button, DrawingArea, Label, Orientation};
+use std::cell::Cell;
+use std::f64::consts::PI;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::services::disk_cache::DownloadProgress;
+use crate::ui::info_panel::format_byte_size;
+
+/// Downloads at least this big, or of unknown size, show their speed and a
+/// cancel button next to the ring.
+const LARGE_DOWNLOAD_BYTES: u64 = 1024 * 1024;
+
+/// Header bar progress ring for the image being loaded. It spins while the
+/// size is unknown and fills up as a download reports progress.
+#[derive(Clone)]
+pub struct DownloadIndicator {
+    container: GtkBox,
+    ring: DrawingArea,
+    details: Label,
+    cancel_button: Button,
+    fraction: Rc<Cell<Option<f64>>>,
+    started: Rc<Cell<Instant>>,
+}
+
+impl DownloadIndicator {
+    pub fn new() -> Self {
+        let container = GtkBox::new(Orientation::Horizontal, 6);
+        container.set_visible(false);
+
+        let fraction = Rc::new(Cell::new(None));
+        let ring = create_ring(Rc::clone(&fraction));
+        ring.set_size_request(24, 24);
+        container.append(&ring);
+
+        let details = Label::new(None);
+        details.add_css_class("dim-label");
+        details.add_css_class("caption");
+        details.add_css_class("numeric");
+        details.set_visible(false);
+        container.append(&details);
+
+        let cancel_button = Button::from_icon_name("process-stop-symbolic");
+        cancel_button.set_tooltip_text(Some("Cancel download"));
+        cancel_button.add_css_class("flat");
+        cancel_button.set_visible(false);
+        container.append(&cancel_button);
+
+        Self {
+            container,
+            ring,
+            details,
+            cancel_button,
+            fraction,
+            started: Rc::new(Cell::new(Instant::now())),
+        }
+    }
+
+    pub fn widget(&self) -> &GtkBox {
+        &self.container
+    }
+
+    pub fn connect_cancel(&self, on_cancel: impl Fn() + 'static) {
+        self.cancel_button.connect_clicked(move |_| on_cancel());
+    }
+
+    /// Shows the spinning ring for a load whose size is not known yet.
+    pub fn start(&self) {
+        self.fraction.set(None);
+        self.started.set(Instant::now());
+        self.details.set_visible(false);
+        self.cancel_button.set_visible(false);
+        self.container.set_visible(true);
+        self.ring.queue_draw();
+    }
+
+    pub fn update(&self, progress: DownloadProgress) {
+        // Each download, and each retry of one, starts by reporting nothing
+        // received; the speed is measured from there rather than from the API
+        // request and any wait before it.
+        if progress.received == 0 {
+            self.started.set(Instant::now());
+        }
+
+        let fraction = progress
+            .total
+            .filter(|total| *total > 0)
+            .map(|total| (progress.received as f64 / total as f64).min(1.0));
+        self.fraction.set(fraction);
+        self.ring.queue_draw();
+
+        let large = progress.total.is_none_or(|total| total >= LARGE_DOWNLOAD_BYTES);
+        self.details.set_visible(large);
+        self.cancel_button.set_visible(large);
+        if !large {
+            return;
+        }
+
+        let elapsed = self.started.get().elapsed().max(Duration::from_millis(1));
+        let speed = (progress.received as f64 / elapsed.as_secs_f64()) as u64;
+        let done = match fraction {
+            Some(fraction) => format!("{:.0}%", fraction * 100.0),
+            None => format_byte_size(progress.received),
+        };
+        self.details.set_text(&format!("{} · {}/s", done, format_byte_size(speed)));
+    }
+
+    pub fn stop(&self) {
+        self.container.set_visible(false);
+    }
+}
+
+impl Default for DownloadIndicator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn create_ring(fraction: Rc<Cell<Option<f64>>>) -> DrawingArea {
+    let drawing_area = DrawingArea::new();
+    let angle = Rc::new(Cell::new(0.0));
+
+    let angle_clone = Rc::clone(&angle);
+    drawing_area.set_draw_func(move |_, cr, width, height| {
+        let center_x = width as f64 / 2.0;
+        let center_y = height as f64 / 2.0;
+        let radius = (width.min(height) as f64 / 2.0) - 2.0;
+
+        cr.set_source_rgba(0.0, 0.0, 0.0, 0.0);
+        cr.paint().unwrap();
+
+        cr.set_source_rgba(0.7, 0.7, 0.7, 0.3);
+        cr.set_line_width(2.0);
+        cr.arc(center_x, center_y, radius, 0.0, 2.0 * PI);
+        cr.stroke().unwrap();
+
+        cr.set_source_rgba(0.0, 0.5, 1.0, 0.8);
+        cr.set_line_width(3.0);
+        cr.set_line_cap(cairo::LineCap::Round);
+
+        // Determinate progress fills clockwise from the top; otherwise a
+        // three-quarter arc keeps spinning.
+        let (start_angle, end_angle) = match fraction.get() {
+            Some(fraction) => (-PI / 2.0, -PI / 2.0 + fraction * 2.0 * PI),
+            None => {
+                let start_angle = angle_clone.get() * PI / 180.0;
+                (start_angle, start_angle + 3.0 * PI / 2.0)
+            }
+        };
+
+        cr.arc(center_x, center_y, radius, start_angle, end_angle);
+        cr.stroke().unwrap();
+    });
+
+    let drawing_area_clone = drawing_area.clone();
+    glib::timeout_add_local(Duration::from_millis(50), move || {
+        if drawing_area_clone.is_mapped() {
+            angle.set((angle.get() + 10.0) % 360.0);
+            drawing_area_clone.queue_draw();
+        }
+        glib::ControlFlow::Continue
+    });
+
+    drawing_area
+}
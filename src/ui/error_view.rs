@@ -0,0 +1,79 @@
+use gtk4::prelude::*;
+use gtk4::{Application, Box as GtkBox, Button, Image, Label, Orientation};
+
+use crate::services::error::ServiceError;
+use crate::ui::settings_window::open_settings_window;
+
+/// Replaces the contents of `container` with a description of `error`, a hint
+/// on how to fix it and a button that calls `on_retry`.
+pub fn show_error_view(container: &GtkBox, error: &ServiceError, on_retry: impl Fn() + 'static) {
+    while let Some(child) = container.first_child() {
+        container.remove(&child);
+    }
+
+    let error_box = GtkBox::new(Orientation::Vertical, 10);
+    error_box.set_halign(gtk4::Align::Center);
+    error_box.set_valign(gtk4::Align::Center);
+    error_box.set_size_request(400, 400);
+
+    let icon_name = match error {
+        ServiceError::Network(_) => "network-offline-symbolic",
+        ServiceError::RateLimited { .. } => "alarm-symbolic",
+        ServiceError::EmptySelection => "preferences-system-symbolic",
+        ServiceError::Io(_) => "drive-harddisk-symbolic",
+        ServiceError::Wallpaper(_) => "preferences-desktop-wallpaper-symbolic",
+        ServiceError::Cancelled => "process-stop-symbolic",
+        ServiceError::HttpStatus(_) | ServiceError::Parse(_) => "dialog-error-symbolic",
+    };
+    let icon = Image::from_icon_name(icon_name);
+    icon.set_pixel_size(64);
+    icon.add_css_class("dim-label");
+    error_box.append(&icon);
+
+    let title_label = Label::new(Some(error.title()));
+    title_label.add_css_class("title-2");
+    error_box.append(&title_label);
+
+    let guidance_label = Label::new(Some(&error.guidance()));
+    guidance_label.set_wrap(true);
+    guidance_label.set_justify(gtk4::Justification::Center);
+    guidance_label.set_max_width_chars(40);
+    error_box.append(&guidance_label);
+
+    let details_label = Label::new(Some(&error.to_string()));
+    details_label.add_css_class("dim-label");
+    details_label.add_css_class("caption");
+    details_label.set_wrap(true);
+    details_label.set_selectable(true);
+    error_box.append(&details_label);
+
+    let button_box = GtkBox::new(Orientation::Horizontal, 10);
+    button_box.set_halign(gtk4::Align::Center);
+    button_box.set_margin_top(10);
+
+    if matches!(error, ServiceError::EmptySelection) {
+        let settings_button = Button::with_label("Open Settings");
+        let container_clone = container.clone();
+        settings_button.connect_clicked(move |_| {
+            if let Some(app) = application_of(&container_clone) {
+                open_settings_window(&app);
+            }
+        });
+        button_box.append(&settings_button);
+    }
+
+    let retry_button = Button::with_label("Retry");
+    retry_button.add_css_class("suggested-action");
+    retry_button.connect_clicked(move |_| on_retry());
+    button_box.append(&retry_button);
+
+    error_box.append(&button_box);
+    container.append(&error_box);
+}
+
+fn application_of(widget: &GtkBox) -> Option<Application> {
+    widget
+        .root()
+        .and_downcast::<gtk4::Window>()
+        .and_then(|window| window.application())
+}
@@ -0,0 +1,250 @@
+use gtk4::prelude::*;
+use gtk4::{
+    Box as GtkBox, Button, Entry, FileChooserAction, FileChooserDialog, HeaderBar, Label,
+    Orientation, ProgressBar, ResponseType, SpinButton, Window
+};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use tokio::sync::{mpsc, watch};
+
+use crate::models::{ImageRecord, UserSettings};
+use crate::services::disk_cache::DiskCache;
+use crate::services::export::{export_images, ExportEvent, ExportOptions};
+use crate::services::naming::TEMPLATE_TOKENS;
+use crate::services::runtime;
+
+/// Opens a dialog that saves `images` into a chosen folder, showing progress
+/// and allowing the export to be cancelled.
+pub fn open_export_dialog(parent: &Window, images: Vec<ImageRecord>) {
+    if images.is_empty() {
+        println!("Nothing to export");
+        return;
+    }
+
+    let export_window = Window::builder()
+        .transient_for(parent)
+        .title(format!("Export {} images", images.len()))
+        .default_width(480)
+        .resizable(false)
+        .modal(true)
+        .build();
+    if let Some(app) = parent.application() {
+        export_window.set_application(Some(&app));
+    }
+
+    let export_header = HeaderBar::new();
+    export_header.set_show_title_buttons(false);
+    export_window.set_titlebar(Some(&export_header));
+
+    let main_box = GtkBox::new(Orientation::Vertical, 15);
+    main_box.set_margin_top(20);
+    main_box.set_margin_bottom(20);
+    main_box.set_margin_start(20);
+    main_box.set_margin_end(20);
+
+    let config_path = UserSettings::default_config_path();
+    let current_settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+
+    let folder = Rc::new(RefCell::new(default_export_dir()));
+
+    let folder_box = GtkBox::new(Orientation::Horizontal, 10);
+    let folder_label = Label::new(Some("Folder"));
+    folder_label.set_halign(gtk4::Align::Start);
+    folder_label.set_hexpand(true);
+    let folder_button = Button::with_label(&folder.borrow().display().to_string());
+    folder_box.append(&folder_label);
+    folder_box.append(&folder_button);
+    main_box.append(&folder_box);
+
+    let template_box = GtkBox::new(Orientation::Horizontal, 10);
+    let template_label = Label::new(Some("File names"));
+    template_label.set_halign(gtk4::Align::Start);
+    template_label.set_hexpand(true);
+    let template_entry = Entry::new();
+    template_entry.set_text(&current_settings.export_template);
+    template_entry.set_width_chars(28);
+    template_box.append(&template_label);
+    template_box.append(&template_entry);
+    main_box.append(&template_box);
+
+    let tokens_label = Label::new(Some(&format!("Available: {}", TEMPLATE_TOKENS)));
+    tokens_label.add_css_class("dim-label");
+    tokens_label.add_css_class("caption");
+    tokens_label.set_halign(gtk4::Align::End);
+    tokens_label.set_wrap(true);
+    main_box.append(&tokens_label);
+
+    let concurrency_box = GtkBox::new(Orientation::Horizontal, 10);
+    let concurrency_label = Label::new(Some("Parallel downloads"));
+    concurrency_label.set_halign(gtk4::Align::Start);
+    concurrency_label.set_hexpand(true);
+    let concurrency_spin = SpinButton::with_range(1.0, 16.0, 1.0);
+    concurrency_spin.set_value(current_settings.export_concurrency as f64);
+    concurrency_box.append(&concurrency_label);
+    concurrency_box.append(&concurrency_spin);
+    main_box.append(&concurrency_box);
+
+    let progress_bar = ProgressBar::new();
+    progress_bar.set_show_text(true);
+    progress_bar.set_visible(false);
+    main_box.append(&progress_bar);
+
+    let status_label = Label::new(None);
+    status_label.set_halign(gtk4::Align::Start);
+    status_label.set_wrap(true);
+    status_label.set_visible(false);
+    main_box.append(&status_label);
+
+    let button_box = GtkBox::new(Orientation::Horizontal, 10);
+    button_box.set_halign(gtk4::Align::End);
+    let cancel_button = Button::with_label("Cancel");
+    let export_button = Button::with_label("Export");
+    export_button.add_css_class("suggested-action");
+    button_box.append(&cancel_button);
+    button_box.append(&export_button);
+    main_box.append(&button_box);
+
+    export_window.set_child(Some(&main_box));
+
+    let folder_clone = Rc::clone(&folder);
+    let export_window_clone = export_window.clone();
+    folder_button.connect_clicked(move |button| {
+        choose_folder(&export_window_clone, button, &folder_clone);
+    });
+
+    // Holds the cancel switch while an export runs; dropping it also cancels.
+    let cancel_sender: Rc<RefCell<Option<watch::Sender<bool>>>> = Rc::new(RefCell::new(None));
+
+    let cancel_sender_clone = Rc::clone(&cancel_sender);
+    let export_window_clone = export_window.clone();
+    cancel_button.connect_clicked(move |_| {
+        if let Some(sender) = cancel_sender_clone.borrow().as_ref()
+            && !*sender.borrow()
+        {
+            println!("Cancelling export...");
+            let _ = sender.send(true);
+            return;
+        }
+        export_window_clone.close();
+    });
+
+    let cancel_sender_clone = Rc::clone(&cancel_sender);
+    export_window.connect_close_request(move |_| {
+        if let Some(sender) = cancel_sender_clone.borrow().as_ref() {
+            let _ = sender.send(true);
+        }
+        glib::Propagation::Proceed
+    });
+
+    export_button.connect_clicked(move |export_button| {
+        let mut settings = UserSettings::load_from_file(&UserSettings::default_config_path()).unwrap_or_default();
+        settings.export_template = template_entry.text().to_string();
+        settings.export_concurrency = concurrency_spin.value() as usize;
+        if let Err(e) = settings.save_to_file(&UserSettings::default_config_path()) {
+            eprintln!("Error while saving export settings: {}", e);
+        }
+
+        let options = ExportOptions {
+            dir: folder.borrow().clone(),
+            template: settings.export_template.clone(),
+            concurrency: settings.export_concurrency,
+        };
+        println!("Exporting {} images to {:?}", images.len(), options.dir);
+
+        for widget in [folder_box.upcast_ref::<gtk4::Widget>(), template_box.upcast_ref(), concurrency_box.upcast_ref()] {
+            widget.set_sensitive(false);
+        }
+        export_button.set_visible(false);
+        progress_bar.set_visible(true);
+        progress_bar.set_fraction(0.0);
+        progress_bar.set_text(Some(&format!("0 / {}", images.len())));
+
+        let (cancel_tx, cancel_rx) = watch::channel(false);
+        *cancel_sender.borrow_mut() = Some(cancel_tx);
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+
+        let total = images.len();
+        let task = runtime::spawn(export_images(
+            images.clone(),
+            options,
+            DiskCache::for_settings(&settings),
+            cancel_rx,
+            events_tx,
+        ));
+
+        let progress_bar = progress_bar.clone();
+        let status_label = status_label.clone();
+        let cancel_button = cancel_button.clone();
+        let cancel_sender = Rc::clone(&cancel_sender);
+        glib::spawn_future_local(async move {
+            let mut saved = 0;
+            let mut failed = 0;
+            while let Some(event) = events_rx.recv().await {
+                match event {
+                    ExportEvent::Saved { path } => {
+                        println!("Exported {:?}", path);
+                        saved += 1;
+                    }
+                    ExportEvent::Failed { url, error } => {
+                        eprintln!("Could not export {}: {}", url, error);
+                        failed += 1;
+                    }
+                }
+                let done = saved + failed;
+                progress_bar.set_fraction(done as f64 / total as f64);
+                progress_bar.set_text(Some(&format!("{} / {}", done, total)));
+            }
+
+            let cancelled = cancel_sender
+                .borrow_mut()
+                .take()
+                .is_some_and(|sender| *sender.borrow());
+            let mut status = match task.await {
+                Ok(Ok(())) if cancelled => format!("Export cancelled. Saved {} of {} images.", saved, total),
+                Ok(Ok(())) => format!("Saved {} of {} images.", saved, total),
+                Ok(Err(e)) => format!("Export failed: {}", e),
+                Err(_) => "Export stopped unexpectedly.".to_string(),
+            };
+            if failed > 0 {
+                status.push_str(&format!(" {} could not be saved.", failed));
+            }
+
+            status_label.set_text(&status);
+            status_label.set_visible(true);
+            cancel_button.set_label("Close");
+        });
+    });
+
+    export_window.present();
+}
+
+fn choose_folder(parent: &Window, button: &Button, folder: &Rc<RefCell<PathBuf>>) {
+    let dialog = FileChooserDialog::new(
+        Some("Choose export folder"),
+        Some(parent),
+        FileChooserAction::SelectFolder,
+        &[("Cancel", ResponseType::Cancel), ("Select", ResponseType::Accept)],
+    );
+
+    let button = button.clone();
+    let folder = Rc::clone(folder);
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Accept
+            && let Some(path) = dialog.file().and_then(|file| file.path())
+        {
+            button.set_label(&path.display().to_string());
+            *folder.borrow_mut() = path;
+        }
+        dialog.close();
+    });
+
+    dialog.present();
+}
+
+/// Where exports go unless another folder is chosen.
+pub fn default_export_dir() -> PathBuf {
+    let pictures = glib::user_special_dir(glib::UserDirectory::Pictures)
+        .unwrap_or_else(|| PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| ".".to_string())));
+    pictures.join("waifu-generator")
+}
@@ -0,0 +1,133 @@
+use gtk4::prelude::*;
+use gtk4::{
+    Application, Box as GtkBox, Button, FlowBox, HeaderBar, Image, Label, Orientation, Picture,
+    ScrolledWindow, SelectionMode, Window
+};
+use std::rc::Rc;
+
+use crate::services::favorites::{Favorite, FavoritesStore};
+use crate::ui::export_dialog::open_export_dialog;
+use crate::ui::image_loader::load_thumbnail;
+
+const THUMBNAIL_SIZE: i32 = 160;
+
+/// Opens the favorites browser. `on_open` is called with the favorite the user
+/// chose to view in the main window.
+pub fn open_favorites_window(app: &Application, on_open: impl Fn(&Favorite) + 'static) -> Window {
+    let favorites_window = Window::builder()
+        .application(app)
+        .title("Favorites - Waifu Generator")
+        .default_width(720)
+        .default_height(560)
+        .resizable(true)
+        .build();
+
+    let favorites_header = HeaderBar::new();
+    favorites_header.set_show_title_buttons(true);
+    favorites_window.set_titlebar(Some(&favorites_header));
+
+    let export_button = Button::with_label("Export all...");
+    favorites_header.pack_start(&export_button);
+
+    let favorites_window_clone = favorites_window.clone();
+    export_button.connect_clicked(move |_| {
+        let images = FavoritesStore::load()
+            .favorites
+            .into_iter()
+            .map(|favorite| favorite.image)
+            .collect();
+        open_export_dialog(&favorites_window_clone, images);
+    });
+
+    let scrolled = ScrolledWindow::new();
+    let main_box = GtkBox::new(Orientation::Vertical, 15);
+    main_box.set_margin_top(20);
+    main_box.set_margin_bottom(20);
+    main_box.set_margin_start(20);
+    main_box.set_margin_end(20);
+
+    let grid = FlowBox::new();
+    grid.set_selection_mode(SelectionMode::None);
+    grid.set_homogeneous(true);
+    grid.set_column_spacing(10);
+    grid.set_row_spacing(10);
+    grid.set_valign(gtk4::Align::Start);
+    main_box.append(&grid);
+
+    scrolled.set_child(Some(&main_box));
+    favorites_window.set_child(Some(&scrolled));
+
+    populate_favorites(&grid, Rc::new(on_open));
+
+    favorites_window.present();
+    favorites_window
+}
+
+fn populate_favorites(grid: &FlowBox, on_open: Rc<dyn Fn(&Favorite)>) {
+    while let Some(child) = grid.first_child() {
+        grid.remove(&child);
+    }
+
+    let store = FavoritesStore::load();
+    if store.favorites.is_empty() {
+        let empty_label = Label::new(Some("No favorites yet. Use the heart button to keep an image here."));
+        empty_label.add_css_class("dim-label");
+        empty_label.set_wrap(true);
+        grid.insert(&empty_label, -1);
+        return;
+    }
+
+    for favorite in store.favorites.iter().rev() {
+        grid.insert(&create_favorite_card(grid, favorite, Rc::clone(&on_open)), -1);
+    }
+}
+
+fn create_favorite_card(grid: &FlowBox, favorite: &Favorite, on_open: Rc<dyn Fn(&Favorite)>) -> GtkBox {
+    let card = GtkBox::new(Orientation::Vertical, 5);
+
+    let picture = Picture::new();
+    picture.set_size_request(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+    picture.set_tooltip_text(Some(&favorite.image.summary()));
+
+    let open_button = Button::new();
+    open_button.set_child(Some(&picture));
+    open_button.add_css_class("flat");
+    card.append(&open_button);
+
+    let path = favorite.local_path();
+    glib::spawn_future_local(async move {
+        match load_thumbnail(path, THUMBNAIL_SIZE).await {
+            Ok(texture) => picture.set_paintable(Some(&texture)),
+            Err(e) => eprintln!("Error while loading thumbnail: {}", e),
+        }
+    });
+
+    let favorite_clone = favorite.clone();
+    let on_open_clone = Rc::clone(&on_open);
+    open_button.connect_clicked(move |_| {
+        on_open_clone(&favorite_clone);
+    });
+
+    let remove_button = Button::new();
+    let remove_icon = Image::from_icon_name("user-trash-symbolic");
+    remove_button.set_child(Some(&remove_icon));
+    remove_button.set_tooltip_text(Some("Remove from favorites"));
+    remove_button.add_css_class("flat");
+    remove_button.set_halign(gtk4::Align::Center);
+    card.append(&remove_button);
+
+    let grid_clone = grid.clone();
+    let url = favorite.image.url.clone();
+    remove_button.connect_clicked(move |_| {
+        let mut store = FavoritesStore::load();
+        if store.remove(&url) {
+            println!("Removed from favorites: {}", url);
+            if let Err(e) = store.save() {
+                eprintln!("Error while saving favorites: {}", e);
+            }
+        }
+        populate_favorites(&grid_clone, Rc::clone(&on_open));
+    });
+
+    card
+}
@@ -0,0 +1,309 @@
+use gtk4::prelude::*;
+use gtk4::{
+    Application, Box as GtkBox, Button, HeaderBar, Label, ListBox, ListBoxRow, Orientation, ScrolledWindow,
+    SearchEntry, SelectionMode, Spinner, Window
+};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::models::ImageRecord;
+use crate::services::history::{HistoryEntry, HistoryLog};
+use crate::services::runtime;
+use crate::ui::export_dialog::open_export_dialog;
+use crate::ui::settings_window::capitalize_first;
+use crate::ui::similar_window::open_similar_window;
+
+/// Only the most recent matches are listed so huge logs stay responsive.
+const MAX_LISTED_ENTRIES: usize = 500;
+
+/// The loaded log and which entry each row of the list shows.
+#[derive(Default)]
+struct Listing {
+    entries: Vec<HistoryEntry>,
+    /// Index into `entries` for each row, by row position. `None` for date
+    /// headers and the empty-list message.
+    rows: Vec<Option<usize>>,
+}
+
+impl Listing {
+    fn image(&self, row: &ListBoxRow) -> Option<&ImageRecord> {
+        let index = (*self.rows.get(usize::try_from(row.index()).ok()?)?)?;
+        self.entries.get(index).map(|entry| &entry.image)
+    }
+}
+
+/// Opens the history browser. `on_open` is called with the image the user
+/// chose to jump back to.
+pub fn open_history_window(app: &Application, on_open: impl Fn(&ImageRecord) + 'static) -> Window {
+    let history_window = Window::builder()
+        .application(app)
+        .title("History - Waifu Generator")
+        .default_width(560)
+        .default_height(600)
+        .resizable(true)
+        .build();
+
+    let history_header = HeaderBar::new();
+    history_header.set_show_title_buttons(true);
+    history_window.set_titlebar(Some(&history_header));
+
+    let export_button = Button::with_label("Export...");
+    export_button.set_tooltip_text(Some("Save the selected images, or all listed images if none is selected"));
+    history_header.pack_start(&export_button);
+
+    let similar_button = Button::with_label("Similar...");
+    similar_button.set_tooltip_text(Some("Find images that look alike in history, favorites and saved folders"));
+    history_header.pack_end(&similar_button);
+
+    let main_box = GtkBox::new(Orientation::Vertical, 10);
+    main_box.set_margin_top(20);
+    main_box.set_margin_bottom(20);
+    main_box.set_margin_start(20);
+    main_box.set_margin_end(20);
+
+    let search_entry = SearchEntry::new();
+    search_entry.set_placeholder_text(Some("Search by category, source, artist or URL"));
+    main_box.append(&search_entry);
+
+    let list = ListBox::new();
+    list.set_selection_mode(SelectionMode::Multiple);
+    // A click selects rows for export; opening one takes a double-click or Enter.
+    list.set_activate_on_single_click(false);
+    list.add_css_class("boxed-list");
+
+    let scrolled = ScrolledWindow::new();
+    scrolled.set_vexpand(true);
+    scrolled.set_child(Some(&list));
+    main_box.append(&scrolled);
+
+    history_window.set_child(Some(&main_box));
+
+    let on_open: Rc<dyn Fn(&ImageRecord)> = Rc::new(on_open);
+
+    let listing = Rc::new(RefCell::new(Listing::default()));
+    show_loading(&list);
+    search_entry.set_sensitive(false);
+    export_button.set_sensitive(false);
+
+    let list_clone = list.clone();
+    let listing_clone = Rc::clone(&listing);
+    let search_entry_clone = search_entry.clone();
+    let export_button_clone = export_button.clone();
+    glib::spawn_future_local(async move {
+        let entries = runtime::spawn_blocking(HistoryLog::load).await.unwrap_or_else(|e| {
+            eprintln!("Error while reading history: {}", e);
+            Vec::new()
+        });
+        listing_clone.borrow_mut().entries = entries;
+        populate_history(&list_clone, &mut listing_clone.borrow_mut(), &search_entry_clone.text());
+        search_entry_clone.set_sensitive(true);
+        export_button_clone.set_sensitive(true);
+    });
+
+    let list_clone = list.clone();
+    let listing_clone = Rc::clone(&listing);
+    search_entry.connect_search_changed(move |entry| {
+        populate_history(&list_clone, &mut listing_clone.borrow_mut(), &entry.text());
+    });
+
+    let list_clone = list.clone();
+    let listing_clone = Rc::clone(&listing);
+    let history_window_clone = history_window.clone();
+    export_button.connect_clicked(move |_| {
+        let images = images_to_export(&list_clone, &listing_clone.borrow());
+        open_export_dialog(&history_window_clone, images);
+    });
+
+    let app_clone = app.clone();
+    let on_open_clone = Rc::clone(&on_open);
+    similar_button.connect_clicked(move |_| {
+        open_similar_window(&app_clone, Rc::clone(&on_open_clone));
+    });
+
+    list.connect_row_activated(move |_, row| {
+        let image = listing.borrow().image(row).cloned();
+        if let Some(image) = image {
+            println!("Jumping back to: {}", image.url);
+            on_open(&image);
+        }
+    });
+
+    history_window.present();
+    history_window
+}
+
+/// The selected images, or every listed image when nothing is selected, each
+/// URL only once.
+fn images_to_export(list: &ListBox, listing: &Listing) -> Vec<ImageRecord> {
+    let mut rows = list.selected_rows();
+    if rows.is_empty() {
+        let mut child = list.first_child();
+        while let Some(widget) = child {
+            child = widget.next_sibling();
+            if let Ok(row) = widget.downcast::<ListBoxRow>() {
+                rows.push(row);
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    rows.iter()
+        .filter_map(|row| listing.image(row))
+        .filter(|image| seen.insert(image.url.clone()))
+        .cloned()
+        .collect()
+}
+
+fn show_loading(list: &ListBox) {
+    let loading_box = GtkBox::new(Orientation::Horizontal, 10);
+    loading_box.set_halign(gtk4::Align::Center);
+    loading_box.set_margin_top(20);
+    loading_box.set_margin_bottom(20);
+    let spinner = Spinner::new();
+    spinner.start();
+    loading_box.append(&spinner);
+    loading_box.append(&Label::new(Some("Loading history...")));
+
+    let row = ListBoxRow::new();
+    row.set_activatable(false);
+    row.set_selectable(false);
+    row.set_child(Some(&loading_box));
+    list.append(&row);
+}
+
+fn populate_history(list: &ListBox, listing: &mut Listing, query: &str) {
+    while let Some(child) = list.first_child() {
+        list.remove(&child);
+    }
+    listing.rows.clear();
+
+    let entries = &listing.entries;
+    let query = query.trim().to_lowercase();
+    let matches: Vec<(usize, &HistoryEntry)> = entries
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, entry)| query.is_empty() || entry_matches(entry, &query))
+        .take(MAX_LISTED_ENTRIES)
+        .collect();
+
+    if matches.is_empty() {
+        let text = if entries.is_empty() { "No history yet." } else { "No matching images." };
+        let empty_label = Label::new(Some(text));
+        empty_label.add_css_class("dim-label");
+        empty_label.set_margin_top(20);
+        empty_label.set_margin_bottom(20);
+        let row = ListBoxRow::new();
+        row.set_activatable(false);
+        row.set_selectable(false);
+        row.set_child(Some(&empty_label));
+        list.append(&row);
+        listing.rows.push(None);
+        return;
+    }
+
+    let mut rows = Vec::with_capacity(matches.len());
+    let mut current_group = String::new();
+    for (index, entry) in matches {
+        let group = date_group(entry.viewed_at);
+        if group != current_group {
+            list.append(&create_group_row(&group));
+            rows.push(None);
+            current_group = group;
+        }
+        list.append(&create_entry_row(entry));
+        rows.push(Some(index));
+    }
+    listing.rows = rows;
+}
+
+fn entry_matches(entry: &HistoryEntry, query: &str) -> bool {
+    let image = &entry.image;
+    image.tag.to_lowercase().contains(query)
+        || image.url.to_lowercase().contains(query)
+        || image.provider.display_name().contains(query)
+        || image.artist_name().is_some_and(|artist| artist.to_lowercase().contains(query))
+}
+
+fn create_group_row(title: &str) -> ListBoxRow {
+    let label = Label::new(Some(title));
+    label.add_css_class("heading");
+    label.set_halign(gtk4::Align::Start);
+    label.set_margin_top(12);
+    label.set_margin_bottom(4);
+
+    let row = ListBoxRow::new();
+    row.set_activatable(false);
+    row.set_selectable(false);
+    row.set_child(Some(&label));
+    row
+}
+
+fn create_entry_row(entry: &HistoryEntry) -> ListBoxRow {
+    let row_box = GtkBox::new(Orientation::Horizontal, 10);
+    row_box.set_margin_top(6);
+    row_box.set_margin_bottom(6);
+    row_box.set_margin_start(6);
+    row_box.set_margin_end(6);
+
+    let time_label = Label::new(Some(&format_time(entry.viewed_at)));
+    time_label.add_css_class("dim-label");
+    row_box.append(&time_label);
+
+    let text_box = GtkBox::new(Orientation::Vertical, 2);
+    text_box.set_hexpand(true);
+
+    let title = format!("{} · {}", capitalize_first(&entry.image.tag), entry.image.provider.display_name());
+    let title_label = Label::new(Some(&title));
+    title_label.set_halign(gtk4::Align::Start);
+    text_box.append(&title_label);
+
+    let url_label = Label::new(Some(&entry.image.url));
+    url_label.add_css_class("caption");
+    url_label.add_css_class("dim-label");
+    url_label.set_halign(gtk4::Align::Start);
+    url_label.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+    text_box.append(&url_label);
+
+    row_box.append(&text_box);
+
+    let row = ListBoxRow::new();
+    row.set_child(Some(&row_box));
+    row.set_tooltip_text(Some(&entry.image.summary()));
+    row
+}
+
+/// "Today", "Yesterday" or the full local date of `timestamp`.
+fn date_group(timestamp: u64) -> String {
+    let Ok(date) = glib::DateTime::from_unix_local(timestamp as i64) else {
+        return "Unknown date".to_string();
+    };
+    let Ok(now) = glib::DateTime::now_local() else {
+        return "Unknown date".to_string();
+    };
+
+    let same_day = |a: &glib::DateTime, b: &glib::DateTime| {
+        a.year() == b.year() && a.day_of_year() == b.day_of_year()
+    };
+
+    if same_day(&date, &now) {
+        return "Today".to_string();
+    }
+    if let Ok(yesterday) = now.add_days(-1)
+        && same_day(&date, &yesterday)
+    {
+        return "Yesterday".to_string();
+    }
+
+    date.format("%A %e %B %Y")
+        .map(|text| text.to_string())
+        .unwrap_or_else(|_| "Unknown date".to_string())
+}
+
+fn format_time(timestamp: u64) -> String {
+    glib::DateTime::from_unix_local(timestamp as i64)
+        .and_then(|date| date.format("%H:%M"))
+        .map(|text| text.to_string())
+        .unwrap_or_default()
+}
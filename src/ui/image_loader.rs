@@ -0,0 +1,168 @@
+use gtk4::prelude::*;
+use gtk4::gdk;
+use gtk4::gdk_pixbuf;
+use std::path::PathBuf;
+use tokio::sync::watch;
+
+use crate::models::{ImageRecord, UserSettings};
+use crate::services::disk_cache::{DiskCache, DownloadProgress};
+use crate::services::error::ServiceError;
+use crate::services::favorites::FavoritesStore;
+use crate::services::naming::detect_extension;
+use crate::services::runtime;
+
+/// Largest width or height decoded when no display can be queried.
+const FALLBACK_MAX_SIZE: i32 = 4096;
+
+/// A downloaded image, decoded as far as it can be off the main thread.
+pub enum LoadedMedia {
+    Still(gdk::Texture),
+    /// An animated GIF. Its frames are decoded on the main thread as it plays
+    /// (see `ui::playback`), since pixbuf animations cannot cross threads.
+    Animation { data: glib::Bytes, frame_count: usize },
+    /// A WebM or MP4 clip, played with `gtk::MediaFile`.
+    Video(PathBuf),
+}
+
+/// Downloads `image` through the disk cache and prepares it for display,
+/// decoding still images straight from memory into a texture no wider or
+/// taller than `max_size` pixels. Favorites are read from their local copy,
+/// so they load offline. Download progress is reported on `progress`.
+///
+/// Must run on the shared runtime (see `runtime::spawn`); decoding happens on
+/// the blocking pool so large images never stall the GTK main loop.
+pub async fn fetch_media(
+    image: ImageRecord,
+    settings: UserSettings,
+    max_size: i32,
+    progress: Option<watch::Sender<DownloadProgress>>,
+) -> Result<LoadedMedia, ServiceError> {
+    let (path, bytes) = match FavoritesStore::load().local_file(&image.url) {
+        Some(path) => {
+            let bytes = tokio::fs::read(&path).await?;
+            (path, bytes)
+        }
+        None => {
+            let expected_size = image.details.as_ref().map(|details| details.byte_size as u64);
+            DiskCache::for_settings(&settings)
+                .fetch_with_progress(&image.url, expected_size, progress.as_ref())
+                .await?
+        }
+    };
+
+    runtime::spawn_blocking(move || decode_media(path, bytes, max_size)).await?
+}
+
+/// Largest width or height worth decoding an image at: the size of the
+/// biggest monitor, in device pixels. Must be called on the main thread.
+pub fn display_max_size() -> i32 {
+    let Some(display) = gdk::Display::default() else {
+        return FALLBACK_MAX_SIZE;
+    };
+
+    let monitors = display.monitors();
+    (0..monitors.n_items())
+        .filter_map(|position| monitors.item(position).and_downcast::<gdk::Monitor>())
+        .map(|monitor| {
+            let geometry = monitor.geometry();
+            geometry.width().max(geometry.height()) * monitor.scale_factor()
+        })
+        .max()
+        .unwrap_or(FALLBACK_MAX_SIZE)
+}
+
+fn decode_media(path: PathBuf, bytes: Vec<u8>, max_size: i32) -> Result<LoadedMedia, ServiceError> {
+    match detect_extension(&bytes, None) {
+        Some("mp4" | "webm") => return Ok(LoadedMedia::Video(path)),
+        Some("gif") => {
+            let frame_count = gif_frame_count(&bytes);
+            if frame_count > 1 {
+                let data = glib::Bytes::from_owned(bytes);
+                return Ok(LoadedMedia::Animation { data, frame_count });
+            }
+        }
+        _ => {}
+    }
+
+    decode_texture(&bytes, max_size)
+        .map(LoadedMedia::Still)
+        .map_err(|e| ServiceError::Parse(format!("Could not decode image: {}", e)))
+}
+
+/// Decodes `bytes`, scaling the image down while decoding if it is wider or
+/// taller than `max_size`, so oversized images never take full-size memory.
+fn decode_texture(bytes: &[u8], max_size: i32) -> Result<gdk::Texture, glib::Error> {
+    let loader = gdk_pixbuf::PixbufLoader::new();
+    loader.connect_size_prepared(move |loader, width, height| {
+        let longest = width.max(height);
+        if longest > max_size {
+            let scale = max_size as f64 / longest as f64;
+            let scaled_width = ((width as f64 * scale).round() as i32).max(1);
+            let scaled_height = ((height as f64 * scale).round() as i32).max(1);
+            loader.set_size(scaled_width, scaled_height);
+        }
+    });
+    loader.write(bytes)?;
+    loader.close()?;
+
+    let pixbuf = loader.pixbuf().ok_or_else(|| {
+        glib::Error::new(gdk_pixbuf::PixbufError::CorruptImage, "Image contains no picture")
+    })?;
+    Ok(gdk::Texture::for_pixbuf(&pixbuf))
+}
+
+/// Number of frames in GIF data, counted from its image descriptors.
+fn gif_frame_count(bytes: &[u8]) -> usize {
+    if bytes.len() < 13 || !bytes.starts_with(b"GIF") {
+        return 0;
+    }
+
+    // Header and logical screen descriptor, then the global color table.
+    let mut pos = 13 + color_table_size(bytes[10]);
+    let mut frames = 0;
+    while let Some(&block) = bytes.get(pos) {
+        match block {
+            // Extension: introducer, label, then data sub-blocks.
+            0x21 => pos = skip_sub_blocks(bytes, pos + 2),
+            // Image descriptor, local color table, LZW code size, image data.
+            0x2C => {
+                let Some(&flags) = bytes.get(pos + 9) else {
+                    break;
+                };
+                frames += 1;
+                pos = skip_sub_blocks(bytes, pos + 10 + color_table_size(flags) + 1);
+            }
+            // Trailer, or anything that is not valid GIF.
+            _ => break,
+        }
+    }
+    frames
+}
+
+fn color_table_size(flags: u8) -> usize {
+    if flags & 0x80 == 0 {
+        0
+    } else {
+        3 * (1 << ((flags & 0x07) + 1))
+    }
+}
+
+fn skip_sub_blocks(bytes: &[u8], mut pos: usize) -> usize {
+    while let Some(&len) = bytes.get(pos) {
+        pos += 1 + len as usize;
+        if len == 0 {
+            break;
+        }
+    }
+    pos
+}
+
+/// Decodes the image at `path` scaled down to fit in a `size`x`size` square.
+pub async fn load_thumbnail(path: PathBuf, size: i32) -> Result<gdk::Texture, ServiceError> {
+    runtime::spawn_blocking(move || {
+        let pixbuf = gdk_pixbuf::Pixbuf::from_file_at_scale(&path, size, size, true)
+            .map_err(|e| ServiceError::Parse(format!("Could not decode image: {}", e)))?;
+        Ok(gdk::Texture::for_pixbuf(&pixbuf))
+    })
+    .await?
+}
@@ -0,0 +1,180 @@
+use gtk4::prelude::*;
+use gtk4::{
+    Box as GtkBox, FlowBox, Frame, Label, LinkButton, Orientation, Revealer,
+    RevealerTransitionType, SelectionMode, Separator
+};
+
+use crate::models::ImageRecord;
+use crate::ui::settings_window::capitalize_first;
+
+pub fn create_info_panel() -> Revealer {
+    let content = GtkBox::new(Orientation::Vertical, 10);
+    content.set_margin_start(10);
+    content.set_margin_end(10);
+    content.set_width_request(240);
+
+    let revealer = Revealer::new();
+    revealer.set_transition_type(RevealerTransitionType::SlideLeft);
+    revealer.set_reveal_child(false);
+    revealer.set_child(Some(&content));
+
+    update_info_panel(&revealer, None);
+
+    revealer
+}
+
+pub fn update_info_panel(panel: &Revealer, image: Option<&ImageRecord>) {
+    let Some(content) = panel.child().and_downcast::<GtkBox>() else {
+        return;
+    };
+
+    while let Some(child) = content.first_child() {
+        content.remove(&child);
+    }
+
+    let title_label = Label::new(Some("Image Info"));
+    title_label.add_css_class("title-4");
+    title_label.set_halign(gtk4::Align::Start);
+    content.append(&title_label);
+
+    let Some(image) = image else {
+        let empty_label = Label::new(Some("No image loaded"));
+        empty_label.add_css_class("dim-label");
+        empty_label.set_halign(gtk4::Align::Start);
+        content.append(&empty_label);
+        return;
+    };
+
+    append_row(&content, "Category", &format!(
+        "{} ({})",
+        capitalize_first(&image.tag),
+        if image.is_nsfw { "NSFW" } else { "SFW" }
+    ));
+    append_row(&content, "Source", image.provider.display_name());
+
+    let Some(details) = image.details.as_ref() else {
+        let note_label = Label::new(Some(&format!(
+            "{} does not provide credits or image details.",
+            image.provider.display_name()
+        )));
+        note_label.add_css_class("dim-label");
+        note_label.set_wrap(true);
+        note_label.set_xalign(0.0);
+        content.append(&note_label);
+        append_link(&content, "Open original image", &image.url);
+        return;
+    };
+
+    content.append(&Separator::new(Orientation::Horizontal));
+
+    let credits_label = Label::new(Some("Credits"));
+    credits_label.add_css_class("heading");
+    credits_label.set_halign(gtk4::Align::Start);
+    content.append(&credits_label);
+
+    match details.artist.as_ref() {
+        Some(artist) => {
+            append_row(&content, "Artist", &artist.name);
+            if let Some(pixiv) = artist.pixiv.as_deref() {
+                append_link(&content, "Pixiv", pixiv);
+            }
+            if let Some(twitter) = artist.twitter.as_deref() {
+                append_link(&content, "Twitter", twitter);
+            }
+            if let Some(patreon) = artist.patreon.as_deref() {
+                append_link(&content, "Patreon", patreon);
+            }
+            if let Some(deviant_art) = artist.deviant_art.as_deref() {
+                append_link(&content, "DeviantArt", deviant_art);
+            }
+        }
+        None => append_row(&content, "Artist", "Unknown"),
+    }
+
+    if let Some(source) = details.source.as_deref() {
+        append_link(&content, "Original source", source);
+    }
+    append_link(&content, "Open original image", &image.url);
+
+    content.append(&Separator::new(Orientation::Horizontal));
+
+    let details_label = Label::new(Some("Details"));
+    details_label.add_css_class("heading");
+    details_label.set_halign(gtk4::Align::Start);
+    content.append(&details_label);
+
+    append_row(&content, "Resolution", &format!("{} × {}", details.width, details.height));
+    append_row(&content, "Size", &format_byte_size(details.byte_size as u64));
+    append_row(&content, "Uploaded", details.uploaded_at.split('T').next().unwrap_or(&details.uploaded_at));
+
+    if !details.tags.is_empty() {
+        content.append(&Separator::new(Orientation::Horizontal));
+
+        let tags_label = Label::new(Some("Tags"));
+        tags_label.add_css_class("heading");
+        tags_label.set_halign(gtk4::Align::Start);
+        content.append(&tags_label);
+
+        let tags_box = FlowBox::new();
+        tags_box.set_selection_mode(SelectionMode::None);
+        tags_box.set_row_spacing(5);
+        tags_box.set_column_spacing(5);
+        tags_box.set_max_children_per_line(4);
+
+        for tag in &details.tags {
+            let chip_label = Label::new(Some(&tag.name));
+            chip_label.add_css_class("caption");
+            chip_label.set_margin_start(6);
+            chip_label.set_margin_end(6);
+            chip_label.set_margin_top(2);
+            chip_label.set_margin_bottom(2);
+            chip_label.set_tooltip_text(Some(&tag.description));
+
+            let chip = Frame::new(None);
+            chip.set_child(Some(&chip_label));
+            tags_box.insert(&chip, -1);
+        }
+
+        content.append(&tags_box);
+    }
+}
+
+fn append_row(content: &GtkBox, name: &str, value: &str) {
+    let row = GtkBox::new(Orientation::Horizontal, 10);
+
+    let name_label = Label::new(Some(name));
+    name_label.add_css_class("dim-label");
+    name_label.set_halign(gtk4::Align::Start);
+
+    let value_label = Label::new(Some(value));
+    value_label.set_halign(gtk4::Align::End);
+    value_label.set_hexpand(true);
+    value_label.set_wrap(true);
+    value_label.set_selectable(true);
+
+    row.append(&name_label);
+    row.append(&value_label);
+    content.append(&row);
+}
+
+fn append_link(content: &GtkBox, label: &str, uri: &str) {
+    let link = LinkButton::with_label(uri, label);
+    link.set_halign(gtk4::Align::Start);
+    link.set_tooltip_text(Some(uri));
+    content.append(&link);
+}
+
+pub fn format_byte_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
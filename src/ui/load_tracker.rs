@@ -0,0 +1,125 @@
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::rc::Rc;
+use tokio::sync::watch;
+use tokio::task::AbortHandle;
+
+use crate::services::disk_cache::DownloadProgress;
+use crate::services::error::ServiceError;
+use crate::services::runtime;
+use crate::ui::download_indicator::DownloadIndicator;
+
+/// Identifies one load started through a `LoadTracker`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestToken(u64);
+
+/// Keeps track of the one image load the main window is waiting for.
+///
+/// Starting a load cancels the previous one: its task on the runtime is
+/// aborted, which also drops any HTTP transfer still in progress, and its
+/// result is discarded even if it was already on its way. The download
+/// indicator is shown exactly while the active load runs.
+#[derive(Clone)]
+pub struct LoadTracker {
+    indicator: DownloadIndicator,
+    next_token: Rc<Cell<u64>>,
+    active: Rc<RefCell<Option<ActiveLoad>>>,
+}
+
+struct ActiveLoad {
+    token: RequestToken,
+    task: Option<AbortHandle>,
+}
+
+impl LoadTracker {
+    pub fn new(indicator: &DownloadIndicator) -> Self {
+        Self {
+            indicator: indicator.clone(),
+            next_token: Rc::new(Cell::new(0)),
+            active: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Cancels the active load, if any, and makes a new one active.
+    pub fn begin(&self) -> RequestToken {
+        self.cancel();
+
+        let token = RequestToken(self.next_token.get());
+        self.next_token.set(token.0 + 1);
+        *self.active.borrow_mut() = Some(ActiveLoad { token, task: None });
+        self.indicator.start();
+        token
+    }
+
+    /// A channel for the download of `token` to report its progress on. The
+    /// indicator follows it for as long as `token` stays active.
+    pub fn progress(&self, token: RequestToken) -> watch::Sender<DownloadProgress> {
+        let (sender, mut receiver) = watch::channel(DownloadProgress::default());
+
+        let tracker = self.clone();
+        glib::spawn_future_local(async move {
+            while receiver.changed().await.is_ok() && tracker.is_active(token) {
+                let progress = *receiver.borrow_and_update();
+                tracker.indicator.update(progress);
+            }
+        });
+        sender
+    }
+
+    /// Runs `future` on the shared runtime on behalf of `token`.
+    ///
+    /// Returns `None` when the load was cancelled or replaced by a newer one
+    /// before `future` finished, in which case the caller must not touch the
+    /// window.
+    pub async fn run<F, T>(&self, token: RequestToken, future: F) -> Option<Result<T, ServiceError>>
+    where
+        F: Future<Output = Result<T, ServiceError>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let task = runtime::spawn(future);
+        match self.active.borrow_mut().as_mut() {
+            Some(active) if active.token == token => active.task = Some(task.abort_handle()),
+            _ => {
+                task.abort();
+                return None;
+            }
+        }
+
+        let result = task.await;
+        if !self.is_active(token) {
+            return None;
+        }
+        if let Some(active) = self.active.borrow_mut().as_mut() {
+            active.task = None;
+        }
+        Some(result.map_err(ServiceError::from).and_then(|result| result))
+    }
+
+    /// Marks the load of `token` as done, hiding the spinner if it is still
+    /// the active one.
+    pub fn finish(&self, token: RequestToken) {
+        if self.is_active(token) {
+            self.active.borrow_mut().take();
+            self.indicator.stop();
+        }
+    }
+
+    /// Abandons the active load, for instance because an image that needs no
+    /// loading is shown instead.
+    pub fn cancel(&self) {
+        if let Some(active) = self.active.borrow_mut().take()
+            && let Some(task) = active.task
+        {
+            println!("Cancelling stale image load");
+            task.abort();
+        }
+        self.indicator.stop();
+    }
+
+    fn is_active(&self, token: RequestToken) -> bool {
+        self.active
+            .borrow()
+            .as_ref()
+            .is_some_and(|active| active.token == token)
+    }
+}
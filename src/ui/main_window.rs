@@ -1,16 +1,45 @@
 use gtk4::prelude::*;
 use gtk4::{
-    Application, ApplicationWindow, Button, Label, Box as GtkBox, Orientation,
-    HeaderBar, Image, ScrolledWindow, Picture, FileChooserDialog, ResponseType, DrawingArea
+    Application, ApplicationWindow, Button, Box as GtkBox, Orientation,
+    HeaderBar, Image, ScrolledWindow, Picture, FileChooserDialog, ResponseType,
+    Revealer, ToggleButton, Label, MenuButton, Popover, MessageDialog, DialogFlags, MessageType,
+    ButtonsType, EventControllerKey, EventControllerMotion, Overlay
 };
-use gtk4::gio;
-use gtk4::cairo;
+use gtk4::gdk;
+use gtk4::gio::SimpleAction;
 
-use crate::ui::settings_window::open_settings_window;
-use crate::models::{UserSettings, ImageCache};
-use crate::services::fetch_waifu_image_async;
+use crate::ui::download_indicator::DownloadIndicator;
+use crate::ui::error_view::show_error_view;
+use crate::ui::favorites_window::open_favorites_window;
+use crate::ui::history_window::open_history_window;
+use crate::ui::info_panel::{create_info_panel, update_info_panel};
+use crate::ui::load_tracker::LoadTracker;
+use crate::ui::prefetch::{PrefetchedImage, Prefetcher};
+use crate::ui::settings_window::{capitalize_first, open_settings_window};
+use crate::ui::shortcuts::apply_key_bindings;
+use crate::ui::slideshow::Slideshow;
+use crate::ui::texture_cache::TextureCache;
+use crate::models::{UserSettings, ImageCache, ImageRecord, SlideshowSource};
+use crate::services::{fetch_unseen_image_async, runtime};
+use crate::services::disk_cache::DiskCache;
+use crate::services::error::ServiceError;
+use crate::services::favorites::{Favorite, FavoritesStore};
+use crate::services::history::HistoryLog;
+use crate::services::seen::SeenImages;
+use crate::services::export::copy_to_new_file;
+use crate::services::naming::{file_extension, render_file_name};
+use crate::services::wallpaper::set_wallpaper;
+use crate::ui::image_loader::{display_max_size, fetch_media, LoadedMedia};
+use crate::ui::playback::show_media;
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::sync::Mutex;
 use std::cell::RefCell;
+use std::time::Duration;
+
+/// Number of images kept in the in-memory history behind prev/next.
+const HISTORY_SIZE: usize = 20;
 
 pub fn build_main_window(app: &Application) {
     let window = ApplicationWindow::builder()
@@ -43,13 +72,70 @@ pub fn build_main_window(app: &Application) {
     download_button.set_tooltip_text(Some("Download image"));
     download_button.add_css_class("flat");
     
-    let loading_spinner = create_circular_loader();
-    loading_spinner.set_size_request(24, 24);
-    loading_spinner.set_visible(false);
+    let wallpaper_button = Button::new();
+    let wallpaper_icon = Image::from_icon_name("preferences-desktop-wallpaper-symbolic");
+    wallpaper_button.set_child(Some(&wallpaper_icon));
+    wallpaper_button.set_tooltip_text(Some("Set as wallpaper"));
+    wallpaper_button.add_css_class("flat");
+    
+    let slideshow_button = ToggleButton::new();
+    let slideshow_icon = Image::from_icon_name("media-playback-start-symbolic");
+    slideshow_button.set_child(Some(&slideshow_icon));
+    slideshow_button.set_tooltip_text(Some("Start slideshow"));
+    slideshow_button.add_css_class("flat");
+    
+    let fullscreen_button = Button::new();
+    let fullscreen_icon = Image::from_icon_name("view-fullscreen-symbolic");
+    fullscreen_button.set_child(Some(&fullscreen_icon));
+    fullscreen_button.set_tooltip_text(Some("Fullscreen slideshow (Esc to leave)"));
+    fullscreen_button.add_css_class("flat");
+    
+    let download_indicator = DownloadIndicator::new();
+    
+    let favorite_button = ToggleButton::new();
+    let favorite_icon = Image::from_icon_name("emblem-favorite-symbolic");
+    favorite_button.set_child(Some(&favorite_icon));
+    favorite_button.set_tooltip_text(Some("Add to favorites"));
+    favorite_button.add_css_class("flat");
+    favorite_button.set_sensitive(false);
+    
+    let favorites_button = Button::new();
+    let favorites_icon = Image::from_icon_name("view-grid-symbolic");
+    favorites_button.set_child(Some(&favorites_icon));
+    favorites_button.set_tooltip_text(Some("Favorites"));
+    favorites_button.add_css_class("flat");
+    
+    let history_button = Button::new();
+    let history_icon = Image::from_icon_name("document-open-recent-symbolic");
+    history_button.set_child(Some(&history_icon));
+    history_button.set_tooltip_text(Some("History"));
+    history_button.add_css_class("flat");
+    
+    let branches_popover = Popover::new();
+    let branches_button = MenuButton::new();
+    branches_button.set_icon_name("media-playlist-shuffle-symbolic");
+    branches_button.set_tooltip_text(Some("Discarded branches"));
+    branches_button.add_css_class("flat");
+    branches_button.set_popover(Some(&branches_popover));
+    branches_button.set_sensitive(false);
+    
+    let info_button = ToggleButton::new();
+    let info_icon = Image::from_icon_name("dialog-information-symbolic");
+    info_button.set_child(Some(&info_icon));
+    info_button.set_tooltip_text(Some("Image info"));
+    info_button.add_css_class("flat");
     
     header_bar.pack_start(&settings_button);
     header_bar.pack_start(&download_button);
-    header_bar.pack_start(&loading_spinner);
+    header_bar.pack_start(&wallpaper_button);
+    header_bar.pack_start(&favorite_button);
+    header_bar.pack_start(&slideshow_button);
+    header_bar.pack_start(&fullscreen_button);
+    header_bar.pack_start(download_indicator.widget());
+    header_bar.pack_end(&info_button);
+    header_bar.pack_end(&favorites_button);
+    header_bar.pack_end(&history_button);
+    header_bar.pack_end(&branches_button);
     
     window.set_titlebar(Some(&header_bar));
 
@@ -94,263 +180,827 @@ pub fn build_main_window(app: &Application) {
     navigation_box.append(&image_display_container);
     navigation_box.append(&next_button);
 
-    image_container.append(&navigation_box);
+    let info_panel = create_info_panel();
+    info_button
+        .bind_property("active", &info_panel, "reveal-child")
+        .sync_create()
+        .build();
+
+    let content_box = GtkBox::new(Orientation::Horizontal, 10);
+    content_box.set_halign(gtk4::Align::Center);
+    content_box.append(&navigation_box);
+    content_box.append(&info_panel);
+
+    image_container.append(&content_box);
     main_box.append(&image_container);
 
     scrolled.set_child(Some(&main_box));
     window.set_child(Some(&scrolled));
 
-    let image_cache = Rc::new(RefCell::new(ImageCache::new(20)));
+    let seen = Rc::new(RefCell::new(SeenImages::default()));
+    let view = MainView {
+        image_container: image_display_container.clone(),
+        image_cache: Rc::new(RefCell::new(ImageCache::new(HISTORY_SIZE))),
+        prev_button: prev_button.clone(),
+        next_button: next_button.clone(),
+        favorite_button: favorite_button.clone(),
+        branches_button: branches_button.clone(),
+        loads: LoadTracker::new(&download_indicator),
+        info_panel: info_panel.clone(),
+        prefetcher: Prefetcher::new(Rc::clone(&seen)),
+        seen,
+        header_bar: header_bar.clone(),
+        slideshow: Slideshow::new(),
+        slideshow_button: slideshow_button.clone(),
+        textures: TextureCache::new(),
+    };
 
-    let image_cache_clone = Rc::clone(&image_cache);
+    let view_clone = view.clone();
+    download_indicator.connect_cancel(move || {
+        cancel_download(&view_clone);
+    });
+
+    let view_clone = view.clone();
     download_button.connect_clicked(move |_| {
-        download_current_image(&image_cache_clone);
+        download_current_image(&view_clone);
+    });
+
+    let view_clone = view.clone();
+    wallpaper_button.connect_clicked(move |button| {
+        set_current_as_wallpaper(&view_clone, button);
+    });
+
+    let view_clone = view.clone();
+    favorite_button.connect_clicked(move |_| {
+        toggle_favorite(&view_clone);
+    });
+
+    let view_clone = view.clone();
+    favorites_button.connect_clicked(move |_| {
+        let view = view_clone.clone();
+        let favorites_window = open_favorites_window(&view_clone.app(), move |favorite| {
+            show_favorite(&view, favorite);
+        });
+        
+        let view = view_clone.clone();
+        favorites_window.connect_close_request(move |_| {
+            let current = view.image_cache.borrow().get_current_image().cloned();
+            update_favorite_button(&view, current.as_ref());
+            glib::Propagation::Proceed
+        });
+    });
+
+    let view_clone = view.clone();
+    history_button.connect_clicked(move |_| {
+        let view = view_clone.clone();
+        open_history_window(&view_clone.app(), move |image| {
+            jump_to_image(&view, image);
+        });
     });
 
-    let image_display_container_clone = image_display_container.clone();
-    let image_cache_clone = Rc::clone(&image_cache);
-    let prev_button_clone = prev_button.clone();
-    let next_button_clone = next_button.clone();
-    let loading_spinner_clone = loading_spinner.clone();
+    let view_clone = view.clone();
+    branches_popover.connect_show(move |popover| {
+        populate_branches_popover(&view_clone, popover);
+    });
+
+    let view_clone = view.clone();
+    slideshow_button.connect_toggled(move |button| {
+        if button.is_active() {
+            start_slideshow(&view_clone);
+        } else {
+            stop_slideshow(&view_clone);
+        }
+    });
+
+    let view_clone = view.clone();
+    fullscreen_button.connect_clicked(move |_| {
+        enter_fullscreen(&view_clone);
+        view_clone.slideshow_button.set_active(true);
+    });
+
+    let view_clone = view.clone();
+    let key_controller = EventControllerKey::new();
+    key_controller.connect_key_pressed(move |_, key, _, _| {
+        if key == gdk::Key::Escape && !view_clone.header_bar.is_visible() {
+            leave_fullscreen(&view_clone);
+            glib::Propagation::Stop
+        } else {
+            glib::Propagation::Proceed
+        }
+    });
+    window.add_controller(key_controller);
+
+    let view_clone = view.clone();
+    window.connect_fullscreened_notify(move |window| {
+        if !window.is_fullscreened() && !view_clone.header_bar.is_visible() {
+            leave_fullscreen(&view_clone);
+        }
+    });
+
+    let slideshow_clone = view.slideshow.clone();
+    window.connect_is_active_notify(move |window| {
+        slideshow_clone.set_focused(window.is_active());
+    });
+
+    let motion_controller = EventControllerMotion::new();
+    let slideshow_clone = view.slideshow.clone();
+    motion_controller.connect_enter(move |_, _, _| {
+        slideshow_clone.set_hovered(true);
+    });
+    let slideshow_clone = view.slideshow.clone();
+    motion_controller.connect_leave(move |_| {
+        slideshow_clone.set_hovered(false);
+    });
+    image_display_container.add_controller(motion_controller);
+
+    install_actions(app, &window, &view);
+
+    let view_clone = view.clone();
     prev_button.connect_clicked(move |_| {
-        navigate_previous(&image_display_container_clone, &image_cache_clone, &prev_button_clone, &next_button_clone, &loading_spinner_clone);
+        navigate_previous(&view_clone);
     });
 
-    let image_display_container_clone = image_display_container.clone();
-    let image_cache_clone = Rc::clone(&image_cache);
-    let prev_button_clone = prev_button.clone();
-    let next_button_clone = next_button.clone();
-    let loading_spinner_clone = loading_spinner.clone();
+    let view_clone = view.clone();
     next_button.connect_clicked(move |_| {
-        navigate_or_generate_next(&image_display_container_clone, &image_cache_clone, &prev_button_clone, &next_button_clone, &loading_spinner_clone);
+        navigate_or_generate_next(&view_clone);
     });
 
     window.present();
     
-    generate_new_image(&image_display_container, &image_cache, &loading_spinner);
-    update_navigation_buttons(&image_cache, &prev_button, &next_button);
+    restore_history(&view);
 }
 
-fn generate_new_image(image_container: &GtkBox, image_cache: &Rc<RefCell<ImageCache>>, loading_spinner: &DrawingArea) {
+/// Exposes the main-window operations as `app.*` actions so they can be bound
+/// to keyboard shortcuts. Everything but `app.settings` only works while the
+/// main window is focused, so the keys keep their usual meaning elsewhere.
+fn install_actions(app: &Application, window: &ApplicationWindow, view: &MainView) {
+    let actions = [
+        add_action(app, "prev", {
+            let view = view.clone();
+            move || navigate_previous(&view)
+        }),
+        add_action(app, "next", {
+            let view = view.clone();
+            move || navigate_or_generate_next(&view)
+        }),
+        add_action(app, "save", {
+            let view = view.clone();
+            move || download_current_image(&view)
+        }),
+        add_action(app, "favorite", {
+            let view = view.clone();
+            move || toggle_favorite(&view)
+        }),
+        add_action(app, "fullscreen", {
+            let view = view.clone();
+            move || {
+                if view.header_bar.is_visible() {
+                    enter_fullscreen(&view);
+                } else {
+                    leave_fullscreen(&view);
+                }
+            }
+        }),
+    ];
+    
+    window.connect_is_active_notify(move |window| {
+        for action in &actions {
+            action.set_enabled(window.is_active());
+        }
+    });
+    
+    let app_clone = app.clone();
+    add_action(app, "settings", move || open_settings_window(&app_clone));
+    
+    let config_path = UserSettings::default_config_path();
+    let settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+    apply_key_bindings(app, &settings.key_bindings);
+}
+
+fn add_action(app: &Application, name: &str, callback: impl Fn() + 'static) -> SimpleAction {
+    let action = SimpleAction::new(name, None);
+    action.connect_activate(move |_, _| callback());
+    app.add_action(&action);
+    action
+}
+
+/// Reads the history log once, off the main thread, then rebuilds the
+/// in-memory history from its end so that "previous" keeps working after a
+/// restart, and the seen images from all of it. The first image is only
+/// generated afterwards, so that it already avoids repeats.
+fn restore_history(view: &MainView) {
+    let view = view.clone();
+    glib::spawn_future_local(async move {
+        let restored = runtime::spawn_blocking(|| {
+            let entries = HistoryLog::load();
+            let seen = SeenImages::from_history(&entries);
+            let start = entries.len().saturating_sub(HISTORY_SIZE);
+            let recent: Vec<ImageRecord> = entries.into_iter().skip(start).map(|entry| entry.image).collect();
+            (recent, seen)
+        })
+        .await;
+        
+        match restored {
+            Ok((recent, seen)) => {
+                let mut cache = view.image_cache.borrow_mut();
+                // Images shown in the meantime stay the most recent ones.
+                if cache.get_current_image().is_none() {
+                    for image in recent {
+                        cache.add_image(image);
+                    }
+                }
+                view.seen.borrow_mut().merge(seen);
+            }
+            Err(e) => eprintln!("Error while reading history: {}", e),
+        }
+        
+        generate_new_image(&view);
+        update_navigation_buttons(&view);
+        view.prefetcher.refill();
+    });
+}
+
+/// Adds a newly shown image to the in-memory history, the seen images and the
+/// history log.
+fn record_image(view: &MainView, image: &ImageRecord, content_hash: Option<&str>) {
+    view.image_cache.borrow_mut().add_image(image.clone());
+    view.seen.borrow_mut().insert(&image.url, content_hash);
+    HistoryLog::append(image, content_hash);
+}
+
+/// Widgets and state shared by the main window's callbacks.
+#[derive(Clone)]
+struct MainView {
+    image_container: GtkBox,
+    image_cache: Rc<RefCell<ImageCache>>,
+    prev_button: Button,
+    next_button: Button,
+    favorite_button: ToggleButton,
+    branches_button: MenuButton,
+    loads: LoadTracker,
+    info_panel: Revealer,
+    prefetcher: Prefetcher,
+    seen: Rc<RefCell<SeenImages>>,
+    header_bar: HeaderBar,
+    slideshow: Slideshow,
+    slideshow_button: ToggleButton,
+    textures: TextureCache,
+}
+
+impl MainView {
+    fn app(&self) -> Application {
+        self.image_container
+            .root()
+            .and_downcast::<gtk4::Window>()
+            .and_then(|window| window.application())
+            .expect("main window has an application")
+    }
+}
+
+fn generate_new_image(view: &MainView) {
     println!("Generating new image...");
     
-    loading_spinner.set_visible(true);
+    let token = view.loads.begin();
+    let progress = view.loads.progress(token);
     
     let config_path = UserSettings::default_config_path();
     let settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+    let seen = view.seen.borrow().clone();
     
-    let image_container_clone = image_container.clone();
-    let image_cache_clone = Rc::clone(image_cache);
-    let loading_spinner_clone = loading_spinner.clone();
+    let view = view.clone();
     
     glib::spawn_future_local(async move {
-        let rt = tokio::runtime::Runtime::new().unwrap();
+        let result = view
+            .loads
+            .run(token, async move { fetch_unseen_image_async(&settings, &seen, Some(&progress)).await })
+            .await;
+        let Some(result) = result else {
+            println!("Discarding superseded image request");
+            return;
+        };
         
-        match rt.block_on(fetch_waifu_image_async(&settings)) {
-            Ok(image_url) => {
-                println!("Image generated: {}", image_url);
-                
-                loading_spinner_clone.set_visible(false);
+        match result {
+            Ok((image, content_hash)) => {
+                println!("Image generated: {}", image.url);
                 
-                image_cache_clone.borrow_mut().add_image(image_url.clone());
+                // Loading the image itself takes over as the active request.
+                record_image(&view, &image, content_hash.as_deref());
             
-                display_image_with_loader(&image_container_clone, &image_url, &loading_spinner_clone);
+                display_image_with_loader(&view, &image);
+                update_navigation_buttons(&view);
             }
             Err(e) => {
                 println!("Error during generation: {}", e);
                 
-                loading_spinner_clone.set_visible(false);
+                view.loads.finish(token);
                 
-                while let Some(child) = image_container_clone.first_child() {
-                    image_container_clone.remove(&child);
-                }
+                update_info_panel(&view.info_panel, None);
+                view.favorite_button.set_sensitive(false);
                 
-                let error_label = Label::new(Some(&format!("Error: {}", e)));
-                error_label.add_css_class("title-2");
-                error_label.add_css_class("error");
-                image_container_clone.append(&error_label);
+                let view_retry = view.clone();
+                show_error_view(&view.image_container, &e, move || {
+                    generate_new_image(&view_retry);
+                });
             }
         }
     });
 }
 
-fn display_image_with_loader(image_container: &GtkBox, image_url: &str, loading_spinner: &DrawingArea) {
-    while let Some(child) = image_container.first_child() {
-        image_container.remove(&child);
+fn show_picture(view: &MainView, image: &ImageRecord) -> Picture {
+    while let Some(child) = view.image_container.first_child() {
+        view.image_container.remove(&child);
     }
     
-    loading_spinner.set_visible(true);
+    update_info_panel(&view.info_panel, Some(image));
+    update_favorite_button(view, Some(image));
     
     let picture = Picture::new();
     picture.set_size_request(400, 400);
-    image_container.append(&picture);
+    picture.set_tooltip_text(Some(&image.summary()));
     
-    let picture_clone = picture.clone();
-    let image_url_clone = image_url.to_string();
-    let loading_spinner_clone = loading_spinner.clone();
+    // Playback controls of animations and videos are overlaid on the picture.
+    let overlay = Overlay::new();
+    overlay.set_child(Some(&picture));
+    view.image_container.append(&overlay);
+    
+    picture
+}
+
+fn display_prefetched_image(view: &MainView, prefetched: PrefetchedImage, settings: &UserSettings) {
+    view.loads.cancel();
+    let picture = show_picture(view, &prefetched.image);
+    remember_texture(view, &prefetched.image, &prefetched.media, settings.texture_memory_mb);
+    show_media(&picture, prefetched.media, settings.loop_media);
+}
+
+/// Keeps the decoded texture of a still image within the history's memory
+/// budget of `budget_mb`, so navigating back to it is instant.
+fn remember_texture(view: &MainView, image: &ImageRecord, media: &LoadedMedia, budget_mb: u64) {
+    if let LoadedMedia::Still(texture) = media {
+        view.textures.insert(&image.url, texture, budget_mb as usize * 1024 * 1024);
+    }
+}
+
+fn display_image_with_loader(view: &MainView, image: &ImageRecord) {
+    let picture = show_picture(view, image);
+    
+    if let Some(texture) = view.textures.get(&image.url) {
+        view.loads.cancel();
+        picture.set_paintable(Some(&texture));
+        return;
+    }
+    
+    let token = view.loads.begin();
+    
+    let image = image.clone();
+    let view = view.clone();
+    
+    let config_path = UserSettings::default_config_path();
+    let settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+    let texture_memory_mb = settings.texture_memory_mb;
+    let loop_media = settings.loop_media;
+    let max_size = display_max_size();
     
     glib::spawn_future_local(async move {
-        let rt = tokio::runtime::Runtime::new().unwrap();
+        let progress = view.loads.progress(token);
+        let result = view.loads.run(token, fetch_media(image.clone(), settings, max_size, Some(progress))).await;
+        let Some(result) = result else {
+            println!("Discarding stale image load: {}", image.url);
+            return;
+        };
         
-        if let Ok(response) = rt.block_on(reqwest::get(&image_url_clone)) {
-            if let Ok(bytes) = rt.block_on(response.bytes()) {
-                let temp_dir = std::env::temp_dir();
-                let temp_file = temp_dir.join("waifu_temp.png");
+        view.loads.finish(token);
+        
+        match result {
+            Ok(media) => {
+                remember_texture(&view, &image, &media, texture_memory_mb);
+                show_media(&picture, media, loop_media);
+            }
+            Err(e) => {
+                eprintln!("Error while loading image: {}", e);
                 
-                if let Ok(_) = std::fs::write(&temp_file, bytes) {
-                    let file = gio::File::for_path(&temp_file);
-                    picture_clone.set_file(Some(&file));
-                    
-                    loading_spinner_clone.set_visible(false);
-                    
-                    let temp_file_clone = temp_file.clone();
-                    glib::timeout_future_seconds(5).await;
-                    let _ = std::fs::remove_file(&temp_file_clone);
-                }
+                let view_retry = view.clone();
+                show_error_view(&view.image_container, &e, move || {
+                    display_image_with_loader(&view_retry, &image);
+                });
             }
         }
     });
 }
 
-fn navigate_previous(
-    image_container: &GtkBox,
-    image_cache: &Rc<RefCell<ImageCache>>,
-    prev_button: &Button,
-    next_button: &Button,
-    loading_spinner: &DrawingArea
-) {
-    let image_url = {
-        let mut cache = image_cache.borrow_mut();
-        cache.go_previous().map(|url| url.clone())
+/// Stops the download in progress and offers to start it again.
+fn cancel_download(view: &MainView) {
+    view.loads.cancel();
+    
+    let view_retry = view.clone();
+    show_error_view(&view.image_container, &ServiceError::Cancelled, move || {
+        let current_image = view_retry.image_cache.borrow().get_current_image().cloned();
+        match current_image {
+            Some(image) => display_image_with_loader(&view_retry, &image),
+            None => generate_new_image(&view_retry),
+        }
+    });
+}
+
+fn navigate_previous(view: &MainView) {
+    let image = {
+        let mut cache = view.image_cache.borrow_mut();
+        cache.go_previous().cloned()
     };
     
-    if let Some(url) = image_url {
-        display_image_with_loader(image_container, &url, loading_spinner);
-        update_navigation_buttons(image_cache, prev_button, next_button);
+    if let Some(image) = image {
+        display_image_with_loader(view, &image);
+        update_navigation_buttons(view);
     }
 }
 
-fn navigate_or_generate_next(
-    image_container: &GtkBox, 
-    image_cache: &Rc<RefCell<ImageCache>>, 
-    prev_button: &Button, 
-    next_button: &Button,
-    loading_spinner: &DrawingArea
-) {
-    let image_url = {
-        let mut cache = image_cache.borrow_mut();
-        cache.go_next().map(|url| url.clone())
+fn navigate_or_generate_next(view: &MainView) {
+    let image = {
+        let mut cache = view.image_cache.borrow_mut();
+        cache.go_next().cloned()
     };
     
-    if let Some(url) = image_url {
-        display_image_with_loader(image_container, &url, loading_spinner);
-        update_navigation_buttons(image_cache, prev_button, next_button);
+    if let Some(image) = image {
+        display_image_with_loader(view, &image);
+        update_navigation_buttons(view);
+        return;
+    }
+    
+    let config_path = UserSettings::default_config_path();
+    let settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+    
+    if let Some(prefetched) = view.prefetcher.take_next(&settings) {
+        println!("Showing prefetched image: {}", prefetched.image.url);
+        record_image(view, &prefetched.image, prefetched.content_hash.as_deref());
+        display_prefetched_image(view, prefetched, &settings);
     } else {
-        generate_new_image(image_container, image_cache, loading_spinner);
-        update_navigation_buttons(image_cache, prev_button, next_button);
+        generate_new_image(view);
     }
+    update_navigation_buttons(view);
+    view.prefetcher.refill();
 }
 
-fn update_navigation_buttons(image_cache: &Rc<RefCell<ImageCache>>, prev_button: &Button, next_button: &Button) {
+fn update_navigation_buttons(view: &MainView) {
     let can_go_prev = {
-        let cache = image_cache.borrow();
+        let cache = view.image_cache.borrow();
         cache.can_go_previous()
     };
     
-    prev_button.set_sensitive(can_go_prev);
-    next_button.set_sensitive(true);
+    view.prev_button.set_sensitive(can_go_prev);
+    view.next_button.set_sensitive(true);
+    view.branches_button.set_sensitive(!view.image_cache.borrow().branches.is_empty());
 }
 
-fn download_current_image(image_cache: &Rc<RefCell<ImageCache>>) {
-    let current_image_url = {
-        let cache = image_cache.borrow();
-        cache.get_current_image().map(|url| url.clone())
-    };
+/// Lists the timelines left behind by generating from the middle of history,
+/// newest first, each with a button to switch back to it.
+fn populate_branches_popover(view: &MainView, popover: &Popover) {
+    let content = GtkBox::new(Orientation::Vertical, 6);
+    content.set_margin_top(6);
+    content.set_margin_bottom(6);
+    content.set_margin_start(6);
+    content.set_margin_end(6);
+    
+    let title_label = Label::new(Some("Discarded branches"));
+    title_label.add_css_class("heading");
+    title_label.set_halign(gtk4::Align::Start);
+    content.append(&title_label);
     
-    if let Some(image_url) = current_image_url {
-        let dialog = FileChooserDialog::new(
-            Some("Save image"),
-            None::<&gtk4::Window>,
-            gtk4::FileChooserAction::Save,
-            &[
-                ("Cancel", ResponseType::Cancel),
-                ("Save", ResponseType::Accept),
-            ]
-        );
+    let branches = view.image_cache.borrow().branches.clone();
+    for (index, branch) in branches.iter().enumerate().rev() {
+        let discarded = branch.discarded();
+        let Some(first) = discarded.first() else {
+            continue;
+        };
         
-        let default_name = format!("waifu_{}.png", 
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-        );
+        let text = match branch.parent() {
+            Some(parent) => format!(
+                "{} image(s) after a {} image, starting with {}",
+                discarded.len(),
+                capitalize_first(&parent.tag),
+                capitalize_first(&first.tag)
+            ),
+            None => format!("{} image(s), starting with {}", discarded.len(), capitalize_first(&first.tag)),
+        };
         
-        dialog.set_current_name(&default_name);
+        let restore_button = Button::with_label(&text);
+        restore_button.add_css_class("flat");
+        restore_button.set_tooltip_text(Some(&first.summary()));
+        content.append(&restore_button);
         
-        let image_url_clone = image_url.clone();
-        dialog.connect_response(move |dialog, response| {
-            if response == ResponseType::Accept {
-                if let Some(file) = dialog.file() {
-                    if let Some(path) = file.path() {
-                        let rt = tokio::runtime::Runtime::new().unwrap();
-                        if let Ok(response) = rt.block_on(reqwest::get(&image_url_clone)) {
-                            if let Ok(bytes) = rt.block_on(response.bytes()) {
-                                if let Err(e) = std::fs::write(&path, bytes) {
-                                    eprintln!("Error during save: {}", e);
-                                } else {
-                                    println!("Image saved: {:?}", path);
-                                }
-                            }
-                        }
-                    }
-                }
+        let view_clone = view.clone();
+        let popover_clone = popover.clone();
+        restore_button.connect_clicked(move |_| {
+            popover_clone.popdown();
+            restore_branch(&view_clone, index);
+        });
+    }
+    
+    popover.set_child(Some(&content));
+}
+
+fn restore_branch(view: &MainView, index: usize) {
+    let image = view.image_cache.borrow_mut().restore_branch(index).cloned();
+    
+    if let Some(image) = image {
+        println!("Restored branch starting at: {}", image.url);
+        display_image_with_loader(view, &image);
+    }
+    update_navigation_buttons(view);
+}
+
+/// Shows an image that is already in the history log without logging it again.
+fn jump_to_image(view: &MainView, image: &ImageRecord) {
+    view.image_cache.borrow_mut().add_image(image.clone());
+    display_image_with_loader(view, image);
+    update_navigation_buttons(view);
+}
+
+fn start_slideshow(view: &MainView) {
+    let config_path = UserSettings::default_config_path();
+    let settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+    let interval = Duration::from_secs(settings.slideshow_interval_secs.max(1) as u64);
+    
+    println!(
+        "Starting slideshow: {} every {}s",
+        settings.slideshow_source.display_name(),
+        interval.as_secs()
+    );
+    
+    // The source is fixed for the whole run, and history and favorites are
+    // read once rather than at every tick.
+    let source = settings.slideshow_source;
+    let view_clone = view.clone();
+    let run = view.slideshow.start(interval, move || {
+        advance_slideshow(&view_clone, source);
+    });
+    
+    let load_playlist = match source {
+        SlideshowSource::Fresh => None,
+        SlideshowSource::History => Some(runtime::spawn_blocking(|| {
+            HistoryLog::load().into_iter().rev().map(|entry| entry.image).collect::<Vec<_>>()
+        })),
+        SlideshowSource::Favorites => Some(runtime::spawn_blocking(|| {
+            FavoritesStore::load().favorites.into_iter().map(|favorite| favorite.image).collect::<Vec<_>>()
+        })),
+    };
+    if let Some(load_playlist) = load_playlist {
+        let slideshow = view.slideshow.clone();
+        glib::spawn_future_local(async move {
+            match load_playlist.await {
+                Ok(images) => slideshow.set_playlist(run, images),
+                Err(e) => eprintln!("Error while loading the slideshow: {}", e),
             }
-            dialog.close();
         });
-        
-        dialog.present();
-    } else {
-        println!("No image to download");
     }
+    
+    let icon = Image::from_icon_name("media-playback-pause-symbolic");
+    view.slideshow_button.set_child(Some(&icon));
+    view.slideshow_button.set_tooltip_text(Some("Stop slideshow"));
 }
 
-fn create_circular_loader() -> DrawingArea {
-    let drawing_area = DrawingArea::new();
-    let mut angle = 0.0;
+fn stop_slideshow(view: &MainView) {
+    view.slideshow.stop();
     
-    drawing_area.set_draw_func(move |_, cr, width, height| {
-        let center_x = width as f64 / 2.0;
-        let center_y = height as f64 / 2.0;
-        let radius = (width.min(height) as f64 / 2.0) - 2.0;
-        
-        cr.set_source_rgba(0.0, 0.0, 0.0, 0.0);
-        cr.paint().unwrap();
-        
-        cr.set_source_rgba(0.7, 0.7, 0.7, 0.3);
-        cr.set_line_width(2.0);
-        cr.arc(center_x, center_y, radius, 0.0, 2.0 * std::f64::consts::PI);
-        cr.stroke().unwrap();
-        
-        cr.set_source_rgba(0.0, 0.5, 1.0, 0.8);
-        cr.set_line_width(3.0);
-        cr.set_line_cap(cairo::LineCap::Round);
-        
-        let start_angle = angle * std::f64::consts::PI / 180.0;
-        let end_angle = start_angle + 3.0 * std::f64::consts::PI / 2.0;
+    let icon = Image::from_icon_name("media-playback-start-symbolic");
+    view.slideshow_button.set_child(Some(&icon));
+    view.slideshow_button.set_tooltip_text(Some("Start slideshow"));
+}
+
+fn advance_slideshow(view: &MainView, source: SlideshowSource) {
+    match source {
+        SlideshowSource::Fresh => navigate_or_generate_next(view),
+        SlideshowSource::History | SlideshowSource::Favorites => {
+            if let Some(image) = view.slideshow.next_in_playlist() {
+                jump_to_image(view, &image);
+            } else {
+                println!("Nothing to show in the slideshow yet");
+            }
+        }
+    }
+}
+
+/// Shows only the image, filling the screen. A running slideshow keeps going;
+/// starting one is left to the slideshow button.
+fn enter_fullscreen(view: &MainView) {
+    let Some(window) = view.image_container.root().and_downcast::<gtk4::Window>() else {
+        return;
+    };
+    
+    view.header_bar.set_visible(false);
+    view.slideshow.set_pause_on_hover(false);
+    window.fullscreen();
+}
+
+fn leave_fullscreen(view: &MainView) {
+    if let Some(window) = view.image_container.root().and_downcast::<gtk4::Window>() {
+        window.unfullscreen();
+    }
+    
+    view.header_bar.set_visible(true);
+    view.slideshow.set_pause_on_hover(true);
+}
+
+fn show_favorite(view: &MainView, favorite: &Favorite) {
+    record_image(view, &favorite.image, None);
+    display_image_with_loader(view, &favorite.image);
+    update_navigation_buttons(view);
+}
+
+fn update_favorite_button(view: &MainView, image: Option<&ImageRecord>) {
+    let is_favorite = image.is_some_and(|image| FavoritesStore::load().contains(&image.url));
+    view.favorite_button.set_sensitive(image.is_some());
+    view.favorite_button.set_active(is_favorite);
+    view.favorite_button.set_tooltip_text(Some(if is_favorite {
+        "Remove from favorites"
+    } else {
+        "Add to favorites"
+    }));
+}
+
+fn toggle_favorite(view: &MainView) {
+    let Some(image) = view.image_cache.borrow().get_current_image().cloned() else {
+        return;
+    };
+    
+    let mut store = FavoritesStore::load();
+    if store.remove(&image.url) {
+        println!("Removed from favorites: {}", image.url);
+        if let Err(e) = store.save() {
+            eprintln!("Error while saving favorites: {}", e);
+        }
+        update_favorite_button(view, Some(&image));
+        return;
+    }
+    
+    let config_path = UserSettings::default_config_path();
+    let settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+    let disk_cache = DiskCache::for_settings(&settings);
+    let view = view.clone();
+    
+    glib::spawn_future_local(async move {
+        let url = image.url.clone();
+        let result = runtime::run(async move {
+            let cached = disk_cache.fetch(&url).await?;
+            let mut store = FavoritesStore::load();
+            store.add(image.clone(), &cached)?;
+            store.save()?;
+            Ok(image)
+        })
+        .await;
         
-        cr.arc(center_x, center_y, radius, start_angle, end_angle);
-        cr.stroke().unwrap();
+        match result {
+            Ok(image) => {
+                println!("Added to favorites: {}", image.url);
+                let current = view.image_cache.borrow().get_current_image().cloned();
+                update_favorite_button(&view, current.as_ref());
+            }
+            Err(e) => {
+                eprintln!("Error while adding favorite: {}", e);
+                view.favorite_button.set_active(false);
+            }
+        }
     });
-    
-    let drawing_area_clone = drawing_area.clone();
-    glib::timeout_add_local(std::time::Duration::from_millis(50), move || {
-        angle += 10.0;
-        if angle >= 360.0 {
-            angle = 0.0;
+}
+
+/// Saves the current image under a name built from the save template, straight
+/// into the default save folder when one is set and through a file dialog
+/// otherwise.
+fn download_current_image(view: &MainView) {
+    let Some(image) = view.image_cache.borrow().get_current_image().cloned() else {
+        println!("No image to download");
+        return;
+    };
+
+    let config_path = UserSettings::default_config_path();
+    let settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+    let disk_cache = DiskCache::for_settings(&settings);
+    let view = view.clone();
+
+    glib::spawn_future_local(async move {
+        let favorite_file = FavoritesStore::load().local_file(&image.url);
+        let image_url = image.url.clone();
+        let source = match favorite_file {
+            Some(path) => Ok(path),
+            None => runtime::run(async move { disk_cache.fetch(&image_url).await }).await,
+        };
+        let source = match source {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("Error during save: {}", e);
+                show_error_dialog(&view, &e);
+                return;
+            }
+        };
+
+        let file_name = render_file_name(&settings.save_template, &image, &file_extension(&source), 1, 1);
+        match settings.save_dir {
+            Some(dir) => {
+                let result = runtime::run(async move {
+                    tokio::fs::create_dir_all(&dir).await?;
+                    copy_to_new_file(&source, &dir, &file_name, &Mutex::new(HashSet::new())).await
+                })
+                .await;
+
+                match result {
+                    Ok(path) => println!("Image saved: {:?}", path),
+                    Err(e) => {
+                        eprintln!("Error during save: {}", e);
+                        show_error_dialog(&view, &e);
+                    }
+                }
+            }
+            None => choose_save_path(&view, source, &file_name),
         }
-        drawing_area_clone.queue_draw();
-        glib::ControlFlow::Continue
     });
+}
+
+fn choose_save_path(view: &MainView, source: PathBuf, file_name: &str) {
+    let parent = view.image_container.root().and_downcast::<gtk4::Window>();
+    let dialog = FileChooserDialog::new(
+        Some("Save image"),
+        parent.as_ref(),
+        gtk4::FileChooserAction::Save,
+        &[
+            ("Cancel", ResponseType::Cancel),
+            ("Save", ResponseType::Accept),
+        ]
+    );
+    dialog.set_current_name(file_name);
+
+    let view = view.clone();
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Accept
+            && let Some(path) = dialog.file().and_then(|file| file.path())
+        {
+            let source = source.clone();
+            let view = view.clone();
+            glib::spawn_future_local(async move {
+                let result = runtime::run(async move {
+                    tokio::fs::copy(&source, &path).await?;
+                    Ok(path)
+                })
+                .await;
+
+                match result {
+                    Ok(path) => println!("Image saved: {:?}", path),
+                    Err(e) => {
+                        eprintln!("Error during save: {}", e);
+                        show_error_dialog(&view, &e);
+                    }
+                }
+            });
+        }
+        dialog.close();
+    });
+
+    dialog.present();
+}
+
+fn set_current_as_wallpaper(view: &MainView, button: &Button) {
+    let Some(image) = view.image_cache.borrow().get_current_image().cloned() else {
+        println!("No image to set as wallpaper");
+        return;
+    };
     
-    drawing_area
+    let config_path = UserSettings::default_config_path();
+    let settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+    let disk_cache = DiskCache::for_settings(&settings);
+    let view = view.clone();
+    let button = button.clone();
+    
+    // The spinner belongs to image loads; the button shows this one is busy.
+    button.set_sensitive(false);
+    
+    glib::spawn_future_local(async move {
+        let result = runtime::run(async move {
+            let cached = disk_cache.fetch(&image.url).await?;
+            runtime::spawn_blocking(move || set_wallpaper(&cached, &settings)).await?
+        })
+        .await;
+        
+        button.set_sensitive(true);
+        
+        match result {
+            Ok(path) => println!("Wallpaper set: {:?}", path),
+            Err(e) => {
+                eprintln!("Error while setting wallpaper: {}", e);
+                show_error_dialog(&view, &e);
+            }
+        }
+    });
+}
+
+/// Reports an error that should not replace the image being shown.
+fn show_error_dialog(view: &MainView, error: &ServiceError) {
+    let parent = view.image_container.root().and_downcast::<gtk4::Window>();
+    let dialog = MessageDialog::new(
+        parent.as_ref(),
+        DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+        MessageType::Error,
+        ButtonsType::Close,
+        error.title(),
+    );
+    dialog.set_secondary_text(Some(&format!("{}\n\n{}", error.guidance(), error)));
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.present();
 }
@@ -1,4 +1,18 @@
+pub mod download_indicator;
+pub mod error_view;
+pub mod export_dialog;
+pub mod favorites_window;
+pub mod history_window;
+pub mod image_loader;
+pub mod info_panel;
+pub mod load_tracker;
 pub mod main_window;
+pub mod playback;
+pub mod prefetch;
 pub mod settings_window;
+pub mod shortcuts;
+pub mod similar_window;
+pub mod slideshow;
+pub mod texture_cache;
 
 pub use main_window::build_main_window;
@@ -0,0 +1,282 @@
+use gtk4::prelude::*;
+use gtk4::{gdk, gdk_pixbuf, gio, Box as GtkBox, MediaFile, Orientation, Overlay, Picture, ToggleButton};
+use glib::SourceId;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use crate::models::UserSettings;
+use crate::ui::image_loader::LoadedMedia;
+
+/// Shows `media` in `picture`. Animations and videos start playing right away,
+/// looping if `looping` is set, and get play/pause and loop buttons over the
+/// picture, which must be the child of an `Overlay`.
+pub fn show_media(picture: &Picture, media: LoadedMedia, looping: bool) {
+    match media {
+        LoadedMedia::Still(texture) => picture.set_paintable(Some(&texture)),
+        LoadedMedia::Animation { data, frame_count } => {
+            let picture = picture.clone();
+            glib::spawn_future_local(async move {
+                let stream = gio::MemoryInputStream::from_bytes(&data);
+                match gdk_pixbuf::PixbufAnimation::from_stream_future(&stream).await {
+                    Ok(animation) => {
+                        let player = AnimationPlayer::new(&picture, &animation, frame_count, looping);
+                        start_playback(&picture, Playback::Animation(player), looping);
+                    }
+                    Err(e) => eprintln!("Could not decode animation: {}", e),
+                }
+            });
+        }
+        LoadedMedia::Video(path) => {
+            let media_file = MediaFile::for_filename(&path);
+            picture.set_paintable(Some(&media_file));
+            start_playback(picture, Playback::Video(media_file), looping);
+        }
+    }
+}
+
+fn start_playback(picture: &Picture, playback: Playback, looping: bool) {
+    playback.set_looping(looping);
+    playback.set_playing(true);
+
+    // Stop playing once the picture is replaced by the next image.
+    let playback_clone = playback.clone();
+    picture.connect_unrealize(move |_| playback_clone.set_playing(false));
+
+    if let Some(overlay) = picture.parent().and_downcast::<Overlay>() {
+        overlay.add_overlay(&create_playback_controls(&playback, looping));
+    }
+}
+
+fn create_playback_controls(playback: &Playback, looping: bool) -> GtkBox {
+    let controls = GtkBox::new(Orientation::Horizontal, 5);
+    controls.add_css_class("osd");
+    controls.add_css_class("linked");
+    controls.set_halign(gtk4::Align::Center);
+    controls.set_valign(gtk4::Align::End);
+    controls.set_margin_bottom(12);
+
+    let play_button = ToggleButton::new();
+    play_button.set_active(playback.is_playing());
+    update_play_button(&play_button);
+    controls.append(&play_button);
+
+    let loop_button = ToggleButton::new();
+    loop_button.set_icon_name("media-playlist-repeat-symbolic");
+    loop_button.set_tooltip_text(Some("Loop"));
+    loop_button.set_active(looping);
+    controls.append(&loop_button);
+
+    let playback_clone = playback.clone();
+    play_button.connect_toggled(move |button| {
+        update_play_button(button);
+        playback_clone.set_playing(button.is_active());
+    });
+
+    // Follow playback that stops on its own, at the end of a clip.
+    let play_button_weak = play_button.downgrade();
+    playback.connect_playing_changed(move |playing| {
+        if let Some(play_button) = play_button_weak.upgrade() {
+            play_button.set_active(playing);
+        }
+    });
+
+    let playback_clone = playback.clone();
+    loop_button.connect_toggled(move |button| {
+        playback_clone.set_looping(button.is_active());
+
+        let config_path = UserSettings::default_config_path();
+        let mut settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+        settings.loop_media = button.is_active();
+        if let Err(e) = settings.save_to_file(&config_path) {
+            eprintln!("Error while saving loop setting: {}", e);
+        }
+    });
+
+    controls
+}
+
+fn update_play_button(button: &ToggleButton) {
+    if button.is_active() {
+        button.set_icon_name("media-playback-pause-symbolic");
+        button.set_tooltip_text(Some("Pause"));
+    } else {
+        button.set_icon_name("media-playback-start-symbolic");
+        button.set_tooltip_text(Some("Play"));
+    }
+}
+
+/// Something the playback controls can drive.
+#[derive(Clone)]
+enum Playback {
+    Animation(AnimationPlayer),
+    Video(MediaFile),
+}
+
+impl Playback {
+    fn is_playing(&self) -> bool {
+        match self {
+            Playback::Animation(player) => player.is_playing(),
+            Playback::Video(media_file) => media_file.is_playing(),
+        }
+    }
+
+    fn set_playing(&self, playing: bool) {
+        match self {
+            Playback::Animation(player) if playing => player.play(),
+            Playback::Animation(player) => player.pause(),
+            Playback::Video(media_file) => media_file.set_playing(playing),
+        }
+    }
+
+    fn set_looping(&self, looping: bool) {
+        match self {
+            Playback::Animation(player) => player.set_looping(looping),
+            Playback::Video(media_file) => media_file.set_loop(looping),
+        }
+    }
+
+    fn connect_playing_changed(&self, f: impl Fn(bool) + 'static) {
+        match self {
+            Playback::Animation(player) => player.connect_playing_changed(f),
+            Playback::Video(media_file) => {
+                media_file.connect_playing_notify(move |media_file| f(media_file.is_playing()));
+            }
+        }
+    }
+}
+
+/// Plays a pixbuf animation frame by frame into a `Picture`.
+///
+/// Time only advances while playing, so pausing and resuming continues from
+/// the same frame. Without looping, playback stops on the last of
+/// `frame_count` frames.
+#[derive(Clone)]
+struct AnimationPlayer {
+    state: Rc<AnimationState>,
+}
+
+struct AnimationState {
+    picture: glib::WeakRef<Picture>,
+    _animation: gdk_pixbuf::PixbufAnimation,
+    iter: gdk_pixbuf::PixbufAnimationIter,
+    start: SystemTime,
+    elapsed: Cell<Duration>,
+    frame: Cell<usize>,
+    frame_count: usize,
+    looping: Cell<bool>,
+    playing: Cell<bool>,
+    timer: RefCell<Option<SourceId>>,
+    on_playing_changed: RefCell<Option<PlayingCallback>>,
+}
+
+type PlayingCallback = Box<dyn Fn(bool)>;
+
+impl AnimationPlayer {
+    fn new(picture: &Picture, animation: &gdk_pixbuf::PixbufAnimation, frame_count: usize, looping: bool) -> Self {
+        let start = SystemTime::now();
+        let player = Self {
+            state: Rc::new(AnimationState {
+                picture: picture.downgrade(),
+                _animation: animation.clone(),
+                iter: animation.iter(Some(start)),
+                start,
+                elapsed: Cell::new(Duration::ZERO),
+                frame: Cell::new(0),
+                frame_count,
+                looping: Cell::new(looping),
+                playing: Cell::new(false),
+                timer: RefCell::new(None),
+                on_playing_changed: RefCell::new(None),
+            }),
+        };
+        player.show_frame();
+        player
+    }
+
+    fn is_playing(&self) -> bool {
+        self.state.playing.get()
+    }
+
+    fn play(&self) {
+        if self.is_playing() {
+            return;
+        }
+        if self.at_last_frame() && !self.state.looping.get() {
+            self.state.elapsed.set(Duration::ZERO);
+            self.state.frame.set(0);
+            self.state.iter.advance(self.state.start);
+            self.show_frame();
+        }
+        self.set_playing_state(true);
+        self.schedule_next_frame();
+    }
+
+    fn pause(&self) {
+        if let Some(source_id) = self.state.timer.borrow_mut().take() {
+            source_id.remove();
+        }
+        self.set_playing_state(false);
+    }
+
+    fn set_looping(&self, looping: bool) {
+        self.state.looping.set(looping);
+    }
+
+    fn connect_playing_changed(&self, f: impl Fn(bool) + 'static) {
+        *self.state.on_playing_changed.borrow_mut() = Some(Box::new(f));
+    }
+
+    fn schedule_next_frame(&self) {
+        let Some(delay) = self.state.iter.delay_time() else {
+            // The animation has no further frames to show.
+            self.set_playing_state(false);
+            return;
+        };
+
+        let player = self.clone();
+        let source_id = glib::timeout_add_local_once(delay, move || {
+            player.state.timer.borrow_mut().take();
+            player.advance(delay);
+        });
+        *self.state.timer.borrow_mut() = Some(source_id);
+    }
+
+    fn advance(&self, delay: Duration) {
+        let elapsed = self.state.elapsed.get() + delay;
+        self.state.elapsed.set(elapsed);
+        self.state.iter.advance(self.state.start + elapsed);
+        self.state.frame.set((self.state.frame.get() + 1) % self.state.frame_count.max(1));
+        if !self.show_frame() {
+            // The picture is gone; nothing left to play into.
+            self.set_playing_state(false);
+        } else if self.at_last_frame() && !self.state.looping.get() {
+            self.set_playing_state(false);
+        } else {
+            self.schedule_next_frame();
+        }
+    }
+
+    fn at_last_frame(&self) -> bool {
+        self.state.frame.get() + 1 >= self.state.frame_count
+    }
+
+    /// Shows the current frame, returning `false` if the picture no longer exists.
+    fn show_frame(&self) -> bool {
+        let Some(picture) = self.state.picture.upgrade() else {
+            return false;
+        };
+        let texture = gdk::Texture::for_pixbuf(&self.state.iter.pixbuf());
+        picture.set_paintable(Some(&texture));
+        true
+    }
+
+    fn set_playing_state(&self, playing: bool) {
+        if self.state.playing.replace(playing) == playing {
+            return;
+        }
+        if let Some(callback) = self.state.on_playing_changed.borrow().as_ref() {
+            callback(playing);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use crate::models::{ImageRecord, UserSettings};
+use crate::services::{fetch_unseen_image_async, runtime};
+use crate::services::error::ServiceError;
+use crate::services::seen::SeenImages;
+use crate::ui::image_loader::{display_max_size, fetch_media, LoadedMedia};
+
+/// An image that has already been resolved, downloaded and decoded.
+pub struct PrefetchedImage {
+    pub image: ImageRecord,
+    pub content_hash: Option<String>,
+    pub media: LoadedMedia,
+}
+
+/// Keeps `UserSettings::prefetch_count` images ready ahead of the history cursor
+/// so that generating a new image does not have to wait for the network.
+#[derive(Clone)]
+pub struct Prefetcher {
+    queue: Rc<RefCell<VecDeque<PrefetchedImage>>>,
+    in_flight: Rc<Cell<usize>>,
+    seen: Rc<RefCell<SeenImages>>,
+}
+
+impl Prefetcher {
+    /// Prefetched images avoid the ones in `seen` as well as each other.
+    pub fn new(seen: Rc<RefCell<SeenImages>>) -> Self {
+        Self {
+            queue: Rc::new(RefCell::new(VecDeque::new())),
+            in_flight: Rc::new(Cell::new(0)),
+            seen,
+        }
+    }
+
+    /// Takes the oldest prefetched image that still matches the current
+    /// category selection, discarding any that no longer do.
+    pub fn take_next(&self, settings: &UserSettings) -> Option<PrefetchedImage> {
+        let mut queue = self.queue.borrow_mut();
+        while let Some(prefetched) = queue.pop_front() {
+            if settings.matches(&prefetched.image) {
+                return Some(prefetched);
+            }
+            println!("Dropping prefetched image outside current selection: {}", prefetched.image.url);
+        }
+        None
+    }
+
+    /// Starts background fetches until the queue plus pending fetches reach the
+    /// configured prefetch count.
+    pub fn refill(&self) {
+        let config_path = UserSettings::default_config_path();
+        let settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+
+        self.queue.borrow_mut().retain(|prefetched| settings.matches(&prefetched.image));
+
+        while self.queue.borrow().len() + self.in_flight.get() < settings.prefetch_count {
+            self.in_flight.set(self.in_flight.get() + 1);
+            self.spawn_fetch(settings.clone());
+        }
+    }
+
+    fn spawn_fetch(&self, settings: UserSettings) {
+        let max_size = display_max_size();
+        let mut seen = self.seen.borrow().clone();
+        for queued in self.queue.borrow().iter() {
+            seen.insert(&queued.image.url, queued.content_hash.as_deref());
+        }
+        let task = runtime::spawn(async move {
+            let (image, content_hash) = fetch_unseen_image_async(&settings, &seen, None).await?;
+            let media = fetch_media(image.clone(), settings, max_size, None).await?;
+            Ok::<_, ServiceError>(PrefetchedImage { image, content_hash, media })
+        });
+
+        let queue = Rc::clone(&self.queue);
+        let in_flight = Rc::clone(&self.in_flight);
+        glib::spawn_future_local(async move {
+            match task.await {
+                Ok(Ok(prefetched)) => {
+                    let config_path = UserSettings::default_config_path();
+                    let settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+                    let is_queued = queue.borrow().iter().any(|queued| {
+                        queued.image.url == prefetched.image.url
+                            || (queued.content_hash.is_some() && queued.content_hash == prefetched.content_hash)
+                    });
+                    if is_queued {
+                        println!("Dropping prefetched image already in the queue: {}", prefetched.image.url);
+                    } else if settings.matches(&prefetched.image) {
+                        println!("Prefetched image: {}", prefetched.image.url);
+                        queue.borrow_mut().push_back(prefetched);
+                    }
+                }
+                Ok(Err(e)) => eprintln!("Error while prefetching: {}", e),
+                Err(_) => eprintln!("Prefetch worker stopped unexpectedly"),
+            }
+            in_flight.set(in_flight.get().saturating_sub(1));
+        });
+    }
+}
@@ -1,12 +1,18 @@
 use gtk4::prelude::*;
 use gtk4::{
     Application, Button, Label, Box as GtkBox, Orientation, 
-    HeaderBar, Window, CheckButton, ScrolledWindow, Separator, Spinner
+    HeaderBar, Window, CheckButton, ScrolledWindow, Separator, Spinner, DropDown, SpinButton, Entry,
+    FileChooserAction, FileChooserDialog, ResponseType
 };
+use std::cell::RefCell;
+use std::path::PathBuf;
 use std::rc::Rc;
 
-use crate::models::UserSettings;
-use crate::services::fetch_waifu_tags_sync;
+use crate::models::{KeyBindings, ProviderKind, SlideshowSource, TagStrategy, UserSettings, WaifuTags, WallpaperBackend};
+use crate::services::{fetch_waifu_tags_async, runtime};
+use crate::services::naming::TEMPLATE_TOKENS;
+use crate::services::providers::ImageProvider;
+use crate::ui::shortcuts::{action_title, apply_key_bindings, is_valid_accel, remove_duplicate_accels, same_accel};
 
 pub fn open_settings_window(app: &Application) {
     let settings_window = Rc::new(Window::builder()
@@ -33,20 +39,277 @@ pub fn open_settings_window(app: &Application) {
     title_label.add_css_class("title-1");
     main_box.append(&title_label);
 
-    let loading_spinner = Spinner::new();
-    loading_spinner.set_size_request(32, 32);
-    loading_spinner.start();
-    
-    let loading_label = Label::new(Some("Loading categories..."));
-    loading_label.add_css_class("title-2");
-    
-    let loading_box = GtkBox::new(Orientation::Vertical, 10);
-    loading_box.set_halign(gtk4::Align::Center);
-    loading_box.set_valign(gtk4::Align::Center);
-    loading_box.append(&loading_spinner);
-    loading_box.append(&loading_label);
-    
-    main_box.append(&loading_box);
+    let config_path = UserSettings::default_config_path();
+    let current_settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+
+    let provider_label = Label::new(Some("Image Source"));
+    provider_label.add_css_class("title-2");
+    main_box.append(&provider_label);
+
+    let provider_names: Vec<&str> = ProviderKind::ALL.iter().map(|p| p.display_name()).collect();
+    let provider_dropdown = DropDown::from_strings(&provider_names);
+    let selected_position = ProviderKind::ALL
+        .iter()
+        .position(|p| *p == current_settings.provider)
+        .unwrap_or(0);
+    provider_dropdown.set_selected(selected_position as u32);
+    main_box.append(&provider_dropdown);
+
+    let provider_separator = Separator::new(Orientation::Horizontal);
+    main_box.append(&provider_separator);
+
+    let categories_box = GtkBox::new(Orientation::Vertical, 15);
+    main_box.append(&categories_box);
+
+    let mix_label = Label::new(Some("Category Mix"));
+    mix_label.add_css_class("title-2");
+    main_box.append(&mix_label);
+
+    let strategy_box = GtkBox::new(Orientation::Horizontal, 10);
+    let strategy_label = Label::new(Some("How categories are picked"));
+    strategy_label.set_halign(gtk4::Align::Start);
+    strategy_label.set_hexpand(true);
+    let strategy_names: Vec<&str> = TagStrategy::ALL.iter().map(|s| s.display_name()).collect();
+    let strategy_dropdown = DropDown::from_strings(&strategy_names);
+    let selected_strategy = TagStrategy::ALL
+        .iter()
+        .position(|s| *s == current_settings.tag_strategy)
+        .unwrap_or(0);
+    strategy_dropdown.set_selected(selected_strategy as u32);
+    strategy_box.append(&strategy_label);
+    strategy_box.append(&strategy_dropdown);
+    main_box.append(&strategy_box);
+
+    let nsfw_ratio_box = GtkBox::new(Orientation::Horizontal, 10);
+    let nsfw_ratio_label = Label::new(Some("NSFW share when both sides are selected (%)"));
+    nsfw_ratio_label.set_halign(gtk4::Align::Start);
+    nsfw_ratio_label.set_hexpand(true);
+    let nsfw_ratio_spin = SpinButton::with_range(0.0, 100.0, 5.0);
+    nsfw_ratio_spin.set_value((current_settings.nsfw_ratio * 100.0).round());
+    nsfw_ratio_box.append(&nsfw_ratio_label);
+    nsfw_ratio_box.append(&nsfw_ratio_spin);
+    main_box.append(&nsfw_ratio_box);
+
+    let mix_hint = Label::new(Some(
+        "The NSFW share only applies to weighted random. Weights next to each category apply to weighted random and the shuffle bag.",
+    ));
+    mix_hint.add_css_class("dim-label");
+    mix_hint.set_halign(gtk4::Align::Start);
+    mix_hint.set_wrap(true);
+    main_box.append(&mix_hint);
+
+    let strategy_dropdown_clone = strategy_dropdown.clone();
+    let update_nsfw_ratio = move |nsfw_ratio_spin: &SpinButton| {
+        let strategy = TagStrategy::ALL.get(strategy_dropdown_clone.selected() as usize).copied();
+        nsfw_ratio_spin.set_sensitive(strategy == Some(TagStrategy::Weighted));
+    };
+    update_nsfw_ratio(&nsfw_ratio_spin);
+    let nsfw_ratio_spin_clone = nsfw_ratio_spin.clone();
+    strategy_dropdown.connect_selected_notify(move |_| update_nsfw_ratio(&nsfw_ratio_spin_clone));
+
+    let performance_label = Label::new(Some("Performance"));
+    performance_label.add_css_class("title-2");
+    main_box.append(&performance_label);
+
+    let cache_size_box = GtkBox::new(Orientation::Horizontal, 10);
+    let cache_size_label = Label::new(Some("Image cache size (MB)"));
+    cache_size_label.set_halign(gtk4::Align::Start);
+    cache_size_label.set_hexpand(true);
+    let cache_size_spin = SpinButton::with_range(50.0, 10000.0, 50.0);
+    cache_size_spin.set_value(current_settings.cache_max_mb as f64);
+    cache_size_box.append(&cache_size_label);
+    cache_size_box.append(&cache_size_spin);
+    main_box.append(&cache_size_box);
+
+    let prefetch_box = GtkBox::new(Orientation::Horizontal, 10);
+    let prefetch_label = Label::new(Some("Images to prefetch"));
+    prefetch_label.set_halign(gtk4::Align::Start);
+    prefetch_label.set_hexpand(true);
+    let prefetch_spin = SpinButton::with_range(0.0, 20.0, 1.0);
+    prefetch_spin.set_value(current_settings.prefetch_count as f64);
+    prefetch_box.append(&prefetch_label);
+    prefetch_box.append(&prefetch_spin);
+    main_box.append(&prefetch_box);
+
+    let texture_memory_box = GtkBox::new(Orientation::Horizontal, 10);
+    let texture_memory_label = Label::new(Some("Decoded images kept in memory (MB)"));
+    texture_memory_label.set_halign(gtk4::Align::Start);
+    texture_memory_label.set_hexpand(true);
+    let texture_memory_spin = SpinButton::with_range(0.0, 4096.0, 32.0);
+    texture_memory_spin.set_value(current_settings.texture_memory_mb as f64);
+    texture_memory_box.append(&texture_memory_label);
+    texture_memory_box.append(&texture_memory_spin);
+    main_box.append(&texture_memory_box);
+
+    let network_label = Label::new(Some("Network"));
+    network_label.add_css_class("title-2");
+    main_box.append(&network_label);
+
+    let retry_box = GtkBox::new(Orientation::Horizontal, 10);
+    let retry_label = Label::new(Some("Attempts per request"));
+    retry_label.set_halign(gtk4::Align::Start);
+    retry_label.set_hexpand(true);
+    let retry_spin = SpinButton::with_range(1.0, 10.0, 1.0);
+    retry_spin.set_value(current_settings.retry.max_attempts as f64);
+    retry_box.append(&retry_label);
+    retry_box.append(&retry_spin);
+    main_box.append(&retry_box);
+
+    let rate_box = GtkBox::new(Orientation::Horizontal, 10);
+    let rate_label = Label::new(Some("API requests per minute"));
+    rate_label.set_halign(gtk4::Align::Start);
+    rate_label.set_hexpand(true);
+    let rate_spin = SpinButton::with_range(1.0, 600.0, 10.0);
+    rate_spin.set_value(current_settings.requests_per_minute as f64);
+    rate_box.append(&rate_label);
+    rate_box.append(&rate_spin);
+    main_box.append(&rate_box);
+
+    let rerolls_box = GtkBox::new(Orientation::Horizontal, 10);
+    let rerolls_label = Label::new(Some("Retries when an image was already seen"));
+    rerolls_label.set_halign(gtk4::Align::Start);
+    rerolls_label.set_hexpand(true);
+    let rerolls_spin = SpinButton::with_range(0.0, 20.0, 1.0);
+    rerolls_spin.set_value(current_settings.duplicate_rerolls as f64);
+    rerolls_box.append(&rerolls_label);
+    rerolls_box.append(&rerolls_spin);
+    main_box.append(&rerolls_box);
+
+    let skip_similar_check = CheckButton::with_label("Also retry when an image looks like one already seen");
+    skip_similar_check.set_active(current_settings.skip_similar_images);
+    main_box.append(&skip_similar_check);
+
+    // Without retries there is no second pick to take instead of a look-alike.
+    skip_similar_check.set_sensitive(rerolls_spin.value() > 0.0);
+    let skip_similar_check_clone = skip_similar_check.clone();
+    rerolls_spin.connect_value_changed(move |spin| skip_similar_check_clone.set_sensitive(spin.value() > 0.0));
+
+    let desktop_label = Label::new(Some("Desktop"));
+    desktop_label.add_css_class("title-2");
+    main_box.append(&desktop_label);
+
+    let wallpaper_box = GtkBox::new(Orientation::Horizontal, 10);
+    let wallpaper_label = Label::new(Some("Wallpaper backend"));
+    wallpaper_label.set_halign(gtk4::Align::Start);
+    wallpaper_label.set_hexpand(true);
+    let mut backend_names = vec!["Automatic"];
+    backend_names.extend(WallpaperBackend::ALL.iter().map(|b| b.display_name()));
+    let wallpaper_dropdown = DropDown::from_strings(&backend_names);
+    let selected_backend = current_settings
+        .wallpaper_backend
+        .and_then(|backend| WallpaperBackend::ALL.iter().position(|b| *b == backend))
+        .map_or(0, |position| position + 1);
+    wallpaper_dropdown.set_selected(selected_backend as u32);
+    wallpaper_box.append(&wallpaper_label);
+    wallpaper_box.append(&wallpaper_dropdown);
+    main_box.append(&wallpaper_box);
+
+    let saving_label = Label::new(Some("Saving"));
+    saving_label.add_css_class("title-2");
+    main_box.append(&saving_label);
+
+    let save_template_box = GtkBox::new(Orientation::Horizontal, 10);
+    let save_template_label = Label::new(Some("File name"));
+    save_template_label.set_halign(gtk4::Align::Start);
+    save_template_label.set_hexpand(true);
+    let save_template_entry = Entry::new();
+    save_template_entry.set_text(&current_settings.save_template);
+    save_template_entry.set_width_chars(24);
+    save_template_box.append(&save_template_label);
+    save_template_box.append(&save_template_entry);
+    main_box.append(&save_template_box);
+
+    let save_tokens_label = Label::new(Some(&format!("Available: {}", TEMPLATE_TOKENS)));
+    save_tokens_label.add_css_class("dim-label");
+    save_tokens_label.add_css_class("caption");
+    save_tokens_label.set_halign(gtk4::Align::End);
+    save_tokens_label.set_wrap(true);
+    main_box.append(&save_tokens_label);
+
+    let save_dir = Rc::new(RefCell::new(current_settings.save_dir.clone()));
+    let save_dir_box = GtkBox::new(Orientation::Horizontal, 10);
+    let save_dir_label = Label::new(Some("Save to"));
+    save_dir_label.set_halign(gtk4::Align::Start);
+    save_dir_label.set_hexpand(true);
+    let save_dir_button = Button::with_label(&save_dir_button_label(save_dir.borrow().as_ref()));
+    let save_dir_clear_button = Button::from_icon_name("edit-clear-symbolic");
+    save_dir_clear_button.set_tooltip_text(Some("Ask every time"));
+    save_dir_box.append(&save_dir_label);
+    save_dir_box.append(&save_dir_button);
+    save_dir_box.append(&save_dir_clear_button);
+    main_box.append(&save_dir_box);
+
+    let save_dir_clone = Rc::clone(&save_dir);
+    let settings_window_clone = Rc::clone(&settings_window);
+    save_dir_button.connect_clicked(move |button| {
+        choose_save_dir(&settings_window_clone, button, &save_dir_clone);
+    });
+
+    let save_dir_clone = Rc::clone(&save_dir);
+    let save_dir_button_clone = save_dir_button.clone();
+    save_dir_clear_button.connect_clicked(move |_| {
+        *save_dir_clone.borrow_mut() = None;
+        save_dir_button_clone.set_label(&save_dir_button_label(None));
+    });
+
+    let slideshow_label = Label::new(Some("Slideshow"));
+    slideshow_label.add_css_class("title-2");
+    main_box.append(&slideshow_label);
+
+    let interval_box = GtkBox::new(Orientation::Horizontal, 10);
+    let interval_label = Label::new(Some("Seconds per image"));
+    interval_label.set_halign(gtk4::Align::Start);
+    interval_label.set_hexpand(true);
+    let interval_spin = SpinButton::with_range(2.0, 3600.0, 1.0);
+    interval_spin.set_value(current_settings.slideshow_interval_secs as f64);
+    interval_box.append(&interval_label);
+    interval_box.append(&interval_spin);
+    main_box.append(&interval_box);
+
+    let slideshow_source_box = GtkBox::new(Orientation::Horizontal, 10);
+    let slideshow_source_label = Label::new(Some("Show images from"));
+    slideshow_source_label.set_halign(gtk4::Align::Start);
+    slideshow_source_label.set_hexpand(true);
+    let source_names: Vec<&str> = SlideshowSource::ALL.iter().map(|s| s.display_name()).collect();
+    let slideshow_source_dropdown = DropDown::from_strings(&source_names);
+    let selected_source = SlideshowSource::ALL
+        .iter()
+        .position(|s| *s == current_settings.slideshow_source)
+        .unwrap_or(0);
+    slideshow_source_dropdown.set_selected(selected_source as u32);
+    slideshow_source_box.append(&slideshow_source_label);
+    slideshow_source_box.append(&slideshow_source_dropdown);
+    main_box.append(&slideshow_source_box);
+
+    let shortcuts_label = Label::new(Some("Keyboard Shortcuts"));
+    shortcuts_label.add_css_class("title-2");
+    main_box.append(&shortcuts_label);
+
+    let shortcuts_hint = Label::new(Some("GTK accelerator syntax, for example <Control>s or Right. Leave empty to disable."));
+    shortcuts_hint.add_css_class("dim-label");
+    shortcuts_hint.set_halign(gtk4::Align::Start);
+    shortcuts_hint.set_wrap(true);
+    main_box.append(&shortcuts_hint);
+
+    let mut shortcut_entries = Vec::new();
+    for action in KeyBindings::ACTIONS {
+        let shortcut_box = GtkBox::new(Orientation::Horizontal, 10);
+        let shortcut_label = Label::new(Some(action_title(action)));
+        shortcut_label.set_halign(gtk4::Align::Start);
+        shortcut_label.set_hexpand(true);
+        let shortcut_entry = Entry::new();
+        shortcut_entry.set_text(current_settings.key_bindings.get(action).unwrap_or_default());
+        shortcut_box.append(&shortcut_label);
+        shortcut_box.append(&shortcut_entry);
+        main_box.append(&shortcut_box);
+        shortcut_entries.push((action, shortcut_entry));
+    }
+    let shortcut_entries = Rc::new(shortcut_entries);
+    for (_, shortcut_entry) in shortcut_entries.iter() {
+        let shortcut_entries_clone = Rc::clone(&shortcut_entries);
+        shortcut_entry.connect_changed(move |_| check_shortcuts(&shortcut_entries_clone));
+    }
+    check_shortcuts(&shortcut_entries);
 
     let button_box = GtkBox::new(Orientation::Horizontal, 10);
     button_box.set_halign(gtk4::Align::End);
@@ -61,9 +324,63 @@ pub fn open_settings_window(app: &Application) {
     save_button.add_css_class("suggested-action");
     save_button.set_sensitive(false);
     let window_clone2 = Rc::clone(&settings_window);
-    let main_box_clone_for_save = main_box.clone();
+    let categories_box_clone_for_save = categories_box.clone();
+    let provider_dropdown_clone_for_save = provider_dropdown.clone();
+    let strategy_dropdown_clone_for_save = strategy_dropdown.clone();
+    let nsfw_ratio_spin_clone_for_save = nsfw_ratio_spin.clone();
+    let cache_size_spin_clone_for_save = cache_size_spin.clone();
+    let prefetch_spin_clone_for_save = prefetch_spin.clone();
+    let texture_memory_spin_clone_for_save = texture_memory_spin.clone();
+    let retry_spin_clone_for_save = retry_spin.clone();
+    let rate_spin_clone_for_save = rate_spin.clone();
+    let rerolls_spin_clone_for_save = rerolls_spin.clone();
+    let skip_similar_check_clone_for_save = skip_similar_check.clone();
+    let wallpaper_dropdown_clone_for_save = wallpaper_dropdown.clone();
+    let save_template_entry_clone_for_save = save_template_entry.clone();
+    let save_dir_clone_for_save = Rc::clone(&save_dir);
+    let interval_spin_clone_for_save = interval_spin.clone();
+    let slideshow_source_dropdown_clone_for_save = slideshow_source_dropdown.clone();
+    let app_clone_for_save = app.clone();
     save_button.connect_clicked(move |_| {
-        save_settings(&main_box_clone_for_save);
+        let config_path = UserSettings::default_config_path();
+        let mut settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+        settings.provider = selected_provider(&provider_dropdown_clone_for_save);
+        settings.tag_strategy = TagStrategy::ALL
+            .get(strategy_dropdown_clone_for_save.selected() as usize)
+            .copied()
+            .unwrap_or_default();
+        settings.nsfw_ratio = nsfw_ratio_spin_clone_for_save.value() / 100.0;
+        settings.cache_max_mb = cache_size_spin_clone_for_save.value() as u64;
+        settings.prefetch_count = prefetch_spin_clone_for_save.value() as usize;
+        settings.texture_memory_mb = texture_memory_spin_clone_for_save.value() as u64;
+        settings.retry.max_attempts = retry_spin_clone_for_save.value() as u32;
+        settings.requests_per_minute = rate_spin_clone_for_save.value() as u32;
+        settings.duplicate_rerolls = rerolls_spin_clone_for_save.value() as u32;
+        settings.skip_similar_images = skip_similar_check_clone_for_save.is_active();
+        settings.wallpaper_backend = selected_wallpaper_backend(&wallpaper_dropdown_clone_for_save);
+        let save_template = save_template_entry_clone_for_save.text().trim().to_string();
+        if !save_template.is_empty() {
+            settings.save_template = save_template;
+        }
+        settings.save_dir = save_dir_clone_for_save.borrow().clone();
+        settings.slideshow_interval_secs = interval_spin_clone_for_save.value() as u32;
+        settings.slideshow_source = SlideshowSource::ALL
+            .get(slideshow_source_dropdown_clone_for_save.selected() as usize)
+            .copied()
+            .unwrap_or_default();
+        for (action, entry) in shortcut_entries.iter() {
+            let accel = entry.text().trim().to_string();
+            if !is_valid_accel(&accel) {
+                eprintln!("Keeping previous shortcut for {}: invalid accelerator {}", action, accel);
+            } else if let Some(other) = shortcut_conflict(&shortcut_entries, action, &accel) {
+                eprintln!("Keeping previous shortcut for {}: {} is also used for {}", action, accel, other);
+            } else {
+                settings.key_bindings.set(action, accel);
+            }
+        }
+        remove_duplicate_accels(&mut settings.key_bindings);
+        apply_key_bindings(&app_clone_for_save, &settings.key_bindings);
+        save_settings(&categories_box_clone_for_save, settings);
         println!("Settings saved!");
         window_clone2.close();
     });
@@ -77,73 +394,193 @@ pub fn open_settings_window(app: &Application) {
 
     settings_window.present();
 
-    match fetch_waifu_tags_sync() {
-        Ok(tags) => {
-            while let Some(child) = loading_box.first_child() {
-                loading_box.remove(&child);
-            }
+    load_categories(&categories_box, &save_button, current_settings.provider, &current_settings);
+
+    let categories_box_clone = categories_box.clone();
+    let save_button_clone = save_button.clone();
+    provider_dropdown.connect_selected_notify(move |dropdown| {
+        let provider = selected_provider(dropdown);
+        println!("Image source changed to {}", provider.display_name());
+        let config_path = UserSettings::default_config_path();
+        let current_settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+        load_categories(&categories_box_clone, &save_button_clone, provider, &current_settings);
+    });
+}
 
-            let config_path = UserSettings::default_config_path();
-            let current_settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+fn selected_provider(dropdown: &DropDown) -> ProviderKind {
+    ProviderKind::ALL
+        .get(dropdown.selected() as usize)
+        .copied()
+        .unwrap_or_default()
+}
 
-            let versatile_label = Label::new(Some("Versatile Categories"));
-            versatile_label.add_css_class("title-2");
-            main_box.insert_child_after(&versatile_label, Some(&main_box.first_child().unwrap()));
+/// `None` stands for the "Automatic" entry at the top of the list.
+fn selected_wallpaper_backend(dropdown: &DropDown) -> Option<WallpaperBackend> {
+    (dropdown.selected() as usize)
+        .checked_sub(1)
+        .and_then(|index| WallpaperBackend::ALL.get(index).copied())
+}
 
-            let versatile_box = GtkBox::new(Orientation::Vertical, 5);
-            
-            for tag in &tags.versatile {
-                let check_button = CheckButton::with_label(&capitalize_first(tag));
-                if current_settings.selected_versatile.contains(tag) {
-                    check_button.set_active(true);
-                }
-                versatile_box.append(&check_button);
-            }
+fn save_dir_button_label(dir: Option<&PathBuf>) -> String {
+    dir.map_or_else(|| "Ask every time".to_string(), |dir| dir.display().to_string())
+}
 
-            main_box.insert_child_after(&versatile_box, Some(&versatile_label));
+fn choose_save_dir(parent: &Window, button: &Button, save_dir: &Rc<RefCell<Option<PathBuf>>>) {
+    let dialog = FileChooserDialog::new(
+        Some("Choose save folder"),
+        Some(parent),
+        FileChooserAction::SelectFolder,
+        &[("Cancel", ResponseType::Cancel), ("Select", ResponseType::Accept)],
+    );
 
-            let separator1 = Separator::new(Orientation::Horizontal);
-            main_box.insert_child_after(&separator1, Some(&versatile_box));
+    let button = button.clone();
+    let save_dir = Rc::clone(save_dir);
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Accept
+            && let Some(path) = dialog.file().and_then(|file| file.path())
+        {
+            button.set_label(&save_dir_button_label(Some(&path)));
+            *save_dir.borrow_mut() = Some(path);
+        }
+        dialog.close();
+    });
 
-            let nsfw_label = Label::new(Some("NSFW Categories"));
-            nsfw_label.add_css_class("title-2");
-            main_box.insert_child_after(&nsfw_label, Some(&separator1));
+    dialog.present();
+}
 
-            let nsfw_box = GtkBox::new(Orientation::Vertical, 5);
-            
-            for tag in &tags.nsfw {
-                let check_button = CheckButton::with_label(&capitalize_first(tag));
-                if current_settings.selected_nsfw.contains(tag) {
-                    check_button.set_active(true);
-                }
-                nsfw_box.append(&check_button);
-            }
+fn load_categories(categories_box: &GtkBox, save_button: &Button, provider: ProviderKind, current_settings: &UserSettings) {
+    while let Some(child) = categories_box.first_child() {
+        categories_box.remove(&child);
+    }
+
+    save_button.set_sensitive(false);
+
+    let loading_spinner = Spinner::new();
+    loading_spinner.set_size_request(32, 32);
+    loading_spinner.start();
+    
+    let loading_label = Label::new(Some("Loading categories..."));
+    loading_label.add_css_class("title-2");
+    
+    let loading_box = GtkBox::new(Orientation::Vertical, 10);
+    loading_box.set_halign(gtk4::Align::Center);
+    loading_box.set_valign(gtk4::Align::Center);
+    loading_box.append(&loading_spinner);
+    loading_box.append(&loading_label);
+    
+    categories_box.append(&loading_box);
 
-            main_box.insert_child_after(&nsfw_box, Some(&nsfw_label));
+    let categories_box = categories_box.clone();
+    let save_button = save_button.clone();
+    let current_settings = current_settings.clone();
 
-            let separator2 = Separator::new(Orientation::Horizontal);
-            main_box.insert_child_after(&separator2, Some(&nsfw_box));
+    glib::spawn_future_local(async move {
+        let tags = runtime::spawn(fetch_waifu_tags_async(provider))
+            .await
+            .unwrap_or_else(|_| provider.fallback_tags());
 
-            save_button.set_sensitive(true);
+        // Switching provider again while loading clears the box and starts a
+        // new load; this one's result is then stale.
+        if loading_box.parent().as_ref() != Some(categories_box.upcast_ref()) {
+            println!("Discarding categories of {}", provider.display_name());
+            return;
         }
-        Err(e) => {
-            while let Some(child) = loading_box.first_child() {
-                loading_box.remove(&child);
+
+        categories_box.remove(&loading_box);
+        populate_categories(&categories_box, &tags, &current_settings);
+        save_button.set_sensitive(true);
+    });
+}
+
+fn populate_categories(categories_box: &GtkBox, tags: &WaifuTags, current_settings: &UserSettings) {
+    let versatile_label = Label::new(Some("Versatile Categories"));
+    versatile_label.add_css_class("title-2");
+    categories_box.append(&versatile_label);
+
+    let versatile_box = GtkBox::new(Orientation::Vertical, 5);
+    
+    for tag in &tags.versatile {
+        let selected = current_settings.selected_versatile.contains(tag);
+        let weight = current_settings.tag_weights.get(tag, false);
+        versatile_box.append(&category_row(tag, selected, weight));
+    }
+
+    categories_box.append(&versatile_box);
+
+    let separator1 = Separator::new(Orientation::Horizontal);
+    categories_box.append(&separator1);
+
+    let nsfw_label = Label::new(Some("NSFW Categories"));
+    nsfw_label.add_css_class("title-2");
+    categories_box.append(&nsfw_label);
+
+    let nsfw_box = GtkBox::new(Orientation::Vertical, 5);
+    
+    for tag in &tags.nsfw {
+        let selected = current_settings.selected_nsfw.contains(tag);
+        let weight = current_settings.tag_weights.get(tag, true);
+        nsfw_box.append(&category_row(tag, selected, weight));
+    }
+
+    categories_box.append(&nsfw_box);
+
+    let separator2 = Separator::new(Orientation::Horizontal);
+    categories_box.append(&separator2);
+}
+
+/// A category's check button followed by its weight.
+fn category_row(tag: &str, selected: bool, weight: u32) -> GtkBox {
+    let row = GtkBox::new(Orientation::Horizontal, 10);
+    let check_button = CheckButton::with_label(&capitalize_first(tag));
+    check_button.set_active(selected);
+    check_button.set_hexpand(true);
+    let weight_spin = SpinButton::with_range(1.0, 10.0, 1.0);
+    weight_spin.set_value(weight as f64);
+    weight_spin.set_tooltip_text(Some("Weight"));
+    row.append(&check_button);
+    row.append(&weight_spin);
+    row
+}
+
+
+/// Flags the shortcut entries holding an invalid accelerator or one that
+/// another action uses too; those are not saved.
+fn check_shortcuts(entries: &[(&'static str, Entry)]) {
+    for (action, entry) in entries {
+        let accel = entry.text().trim().to_string();
+        let problem = if !is_valid_accel(&accel) {
+            Some("Not a valid accelerator".to_string())
+        } else {
+            shortcut_conflict(entries, action, &accel).map(|other| format!("Also used for \"{}\"", action_title(other)))
+        };
+
+        match problem {
+            Some(problem) => {
+                entry.add_css_class("error");
+                entry.set_tooltip_text(Some(&problem));
+            }
+            None => {
+                entry.remove_css_class("error");
+                entry.set_tooltip_text(None);
             }
-            let error_label = Label::new(Some(&format!("Error: {}", e)));
-            error_label.add_css_class("error");
-            loading_box.append(&error_label);
         }
     }
 }
 
+/// Another action whose entry holds the same accelerator as `accel`.
+fn shortcut_conflict(entries: &[(&'static str, Entry)], action: &str, accel: &str) -> Option<&'static str> {
+    entries
+        .iter()
+        .find(|(other, entry)| *other != action && same_accel(accel, entry.text().trim()))
+        .map(|(other, _)| *other)
+}
 
-fn save_settings(main_box: &GtkBox) {
+fn save_settings(categories_box: &GtkBox, mut settings: UserSettings) {
     let mut selected_versatile = Vec::new();
     let mut selected_nsfw = Vec::new();
     let mut is_in_nsfw_section = false;
 
-    let mut child = main_box.first_child();
+    let mut child = categories_box.first_child();
     while let Some(widget) = child {
         if let Some(label) = widget.downcast_ref::<Label>() {
             let label_text = label.text();
@@ -154,12 +591,16 @@ fn save_settings(main_box: &GtkBox) {
             }
         } else if let Some(box_widget) = widget.downcast_ref::<GtkBox>() {
             let mut box_child = box_widget.first_child();
-            while let Some(box_widget_child) = box_child {
-                if let Some(check_button) = box_widget_child.downcast_ref::<CheckButton>() {
+            while let Some(row) = box_child {
+                if let Some(check_button) = row.first_child().and_downcast::<CheckButton>() {
+                    let label = check_button.label().unwrap_or_default();
+                    let tag = label.to_lowercase();
+                    
+                    if let Some(weight_spin) = row.last_child().and_downcast::<SpinButton>() {
+                        settings.tag_weights.set(&tag, is_in_nsfw_section, weight_spin.value() as u32);
+                    }
+                    
                     if check_button.is_active() {
-                        let label = check_button.label().unwrap_or_default();
-                        let tag = label.to_lowercase();
-                        
                         println!("Checkbox found: '{}' (active) - Section: {}", tag, if is_in_nsfw_section { "NSFW" } else { "SFW" });
                         
                         if is_in_nsfw_section {
@@ -169,7 +610,7 @@ fn save_settings(main_box: &GtkBox) {
                         }
                     }
                 }
-                box_child = box_widget_child.next_sibling();
+                box_child = row.next_sibling();
             }
         }
         child = widget.next_sibling();
@@ -178,27 +619,21 @@ fn save_settings(main_box: &GtkBox) {
     println!("Selected SFW tags: {:?}", selected_versatile);
     println!("Selected NSFW tags: {:?}", selected_nsfw);
 
-    let settings = UserSettings {
-        selected_versatile,
-        selected_nsfw,
-    };
+    settings.selected_versatile = selected_versatile;
+    settings.selected_nsfw = selected_nsfw;
 
     let config_path = UserSettings::default_config_path();
-    
-    if let Some(parent) = std::path::Path::new(&config_path).parent() {
-        let _ = std::fs::create_dir_all(parent);
-    }
 
     match settings.save_to_file(&config_path) {
         Ok(_) => {
-            println!("Settings saved in: {}", config_path);
+            println!("Settings saved in: {:?}", config_path);
             println!("Settings saved!");
         },
         Err(e) => eprintln!("Error during save: {}", e),
     }
 }
 
-fn capitalize_first(s: &str) -> String {
+pub fn capitalize_first(s: &str) -> String {
     let mut chars = s.chars();
     match chars.next() {
         #[allow(non_snake_case)]
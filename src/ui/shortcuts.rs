@@ -0,0 +1,129 @@
+use gtk4::prelude::*;
+use gtk4::{Application, ApplicationWindow, Builder, ShortcutsWindow};
+
+use crate::models::KeyBindings;
+
+/// Accelerator of the help overlay itself; it is not rebindable.
+const HELP_OVERLAY_ACCEL: &str = "<Control>question";
+
+/// Human-readable name of each `app.*` action, in the order they are listed.
+pub fn action_title(action: &str) -> &'static str {
+    match action {
+        "prev" => "Previous image",
+        "next" => "Next image",
+        "save" => "Save image",
+        "favorite" => "Add to or remove from favorites",
+        "fullscreen" => "Toggle fullscreen",
+        "settings" => "Open settings",
+        _ => "",
+    }
+}
+
+/// Whether `accel` is empty or a valid GTK accelerator.
+pub fn is_valid_accel(accel: &str) -> bool {
+    accel.is_empty() || gtk4::accelerator_parse(accel).is_some()
+}
+
+/// Whether `a` and `b` are the same non-empty accelerator, however they are
+/// spelled (`<Ctrl>s` and `<Control>s` are the same).
+pub fn same_accel(a: &str, b: &str) -> bool {
+    match (gtk4::accelerator_parse(a), gtk4::accelerator_parse(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Unbinds every action whose accelerator an earlier action already uses, so
+/// one key never triggers two actions.
+pub fn remove_duplicate_accels(bindings: &mut KeyBindings) {
+    for (position, action) in KeyBindings::ACTIONS.iter().enumerate() {
+        let accel = bindings.get(action).unwrap_or_default().to_string();
+        let earlier = KeyBindings::ACTIONS[..position]
+            .iter()
+            .find(|earlier| same_accel(&accel, bindings.get(earlier).unwrap_or_default()));
+        if let Some(earlier) = earlier {
+            eprintln!("Removing shortcut {} from {}: already used for {}", accel, action, earlier);
+            bindings.set(action, String::new());
+        }
+    }
+}
+
+/// Registers `bindings` as the application's accelerators and rebuilds the
+/// shortcuts help overlay of every window so it shows them.
+pub fn apply_key_bindings(app: &Application, bindings: &KeyBindings) {
+    for action in KeyBindings::ACTIONS {
+        let accel = bindings.get(action).unwrap_or_default();
+        let accels: &[&str] = if accel.is_empty() {
+            &[]
+        } else if is_valid_accel(accel) {
+            &[accel]
+        } else {
+            eprintln!("Ignoring invalid shortcut for {}: {}", action, accel);
+            &[]
+        };
+        app.set_accels_for_action(&format!("app.{}", action), accels);
+    }
+    app.set_accels_for_action("win.show-help-overlay", &[HELP_OVERLAY_ACCEL]);
+
+    for window in app.windows() {
+        if let Some(window) = window.downcast_ref::<ApplicationWindow>() {
+            window.set_help_overlay(Some(&build_help_overlay(bindings)));
+        }
+    }
+}
+
+fn build_help_overlay(bindings: &KeyBindings) -> ShortcutsWindow {
+    let mut shortcuts = String::new();
+    for action in KeyBindings::ACTIONS {
+        let accel = bindings.get(action).unwrap_or_default();
+        if accel.is_empty() || !is_valid_accel(accel) {
+            continue;
+        }
+        shortcuts.push_str(&shortcut_xml(action_title(action), accel));
+    }
+    shortcuts.push_str(&shortcut_xml("Show keyboard shortcuts", HELP_OVERLAY_ACCEL));
+
+    let xml = format!(
+        r#"<interface>
+  <object class="GtkShortcutsWindow" id="help_overlay">
+    <property name="modal">1</property>
+    <child>
+      <object class="GtkShortcutsSection">
+        <property name="section-name">shortcuts</property>
+        <child>
+          <object class="GtkShortcutsGroup">
+            <property name="title">Waifu Generator</property>
+{}          </object>
+        </child>
+      </object>
+    </child>
+  </object>
+</interface>"#,
+        shortcuts
+    );
+
+    Builder::from_string(&xml)
+        .object::<ShortcutsWindow>("help_overlay")
+        .expect("help overlay definition is valid")
+}
+
+fn shortcut_xml(title: &str, accel: &str) -> String {
+    format!(
+        r#"            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">{}</property>
+                <property name="accelerator">{}</property>
+              </object>
+            </child>
+"#,
+        escape_xml(title),
+        escape_xml(accel)
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
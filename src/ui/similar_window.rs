@@ -0,0 +1,149 @@
+use gtk4::prelude::*;
+use gtk4::{
+    Application, Box as GtkBox, Button, FlowBox, HeaderBar, Label, Orientation, Picture, ScrolledWindow,
+    SelectionMode, Spinner, Window
+};
+use std::rc::Rc;
+
+use crate::models::{ImageRecord, UserSettings};
+use crate::services::runtime;
+use crate::services::similar::{find_similar, SimilarImage};
+use crate::ui::export_dialog::default_export_dir;
+use crate::ui::image_loader::load_thumbnail;
+
+const THUMBNAIL_SIZE: i32 = 120;
+
+/// Opens the list of images that look alike across history, favorites and
+/// saved folders. `on_open` is called with the image the user chose to view.
+pub fn open_similar_window(app: &Application, on_open: Rc<dyn Fn(&ImageRecord)>) -> Window {
+    let similar_window = Window::builder()
+        .application(app)
+        .title("Similar Images - Waifu Generator")
+        .default_width(720)
+        .default_height(560)
+        .resizable(true)
+        .build();
+
+    let similar_header = HeaderBar::new();
+    similar_header.set_show_title_buttons(true);
+    similar_window.set_titlebar(Some(&similar_header));
+
+    let main_box = GtkBox::new(Orientation::Vertical, 15);
+    main_box.set_margin_top(20);
+    main_box.set_margin_bottom(20);
+    main_box.set_margin_start(20);
+    main_box.set_margin_end(20);
+
+    let status_box = GtkBox::new(Orientation::Horizontal, 10);
+    let spinner = Spinner::new();
+    spinner.start();
+    let status_label = Label::new(Some("Comparing images..."));
+    status_label.add_css_class("dim-label");
+    status_box.append(&spinner);
+    status_box.append(&status_label);
+    main_box.append(&status_box);
+
+    let groups_box = GtkBox::new(Orientation::Vertical, 20);
+    main_box.append(&groups_box);
+
+    let scrolled = ScrolledWindow::new();
+    scrolled.set_child(Some(&main_box));
+    similar_window.set_child(Some(&scrolled));
+
+    let config_path = UserSettings::default_config_path();
+    let settings = UserSettings::load_from_file(&config_path).unwrap_or_default();
+    let mut saved_dirs: Vec<_> = settings.save_dir.iter().cloned().collect();
+    saved_dirs.push(default_export_dir());
+
+    glib::spawn_future_local(async move {
+        let groups = runtime::spawn_blocking(move || find_similar(&settings, &saved_dirs)).await;
+        spinner.stop();
+        spinner.set_visible(false);
+
+        let groups = match groups {
+            Ok(groups) => groups,
+            Err(_) => {
+                status_label.set_text("Could not compare images");
+                return;
+            }
+        };
+
+        status_label.set_text(&match groups.len() {
+            0 => "No similar images found".to_string(),
+            1 => "1 group of similar images".to_string(),
+            count => format!("{} groups of similar images", count),
+        });
+        for group in &groups {
+            groups_box.append(&create_group(group, Rc::clone(&on_open)));
+        }
+    });
+
+    similar_window.present();
+    similar_window
+}
+
+fn create_group(group: &[SimilarImage], on_open: Rc<dyn Fn(&ImageRecord)>) -> GtkBox {
+    let group_box = GtkBox::new(Orientation::Vertical, 10);
+
+    let title = Label::new(Some(&format!("{} similar images", group.len())));
+    title.add_css_class("heading");
+    title.set_halign(gtk4::Align::Start);
+    group_box.append(&title);
+
+    let grid = FlowBox::new();
+    grid.set_selection_mode(SelectionMode::None);
+    grid.set_homogeneous(true);
+    grid.set_column_spacing(10);
+    grid.set_row_spacing(10);
+    grid.set_valign(gtk4::Align::Start);
+    for image in group {
+        grid.insert(&create_image_card(image, Rc::clone(&on_open)), -1);
+    }
+    group_box.append(&grid);
+
+    group_box
+}
+
+fn create_image_card(image: &SimilarImage, on_open: Rc<dyn Fn(&ImageRecord)>) -> GtkBox {
+    let card = GtkBox::new(Orientation::Vertical, 5);
+
+    let picture = Picture::new();
+    picture.set_size_request(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+
+    let open_button = Button::new();
+    open_button.set_child(Some(&picture));
+    open_button.add_css_class("flat");
+    card.append(&open_button);
+
+    match image.record() {
+        Some(record) => {
+            open_button.set_tooltip_text(Some(&record.summary()));
+            let record = record.clone();
+            open_button.connect_clicked(move |_| {
+                on_open(&record);
+            });
+        }
+        None => {
+            open_button.set_tooltip_text(Some(&image.path.display().to_string()));
+            open_button.set_can_target(false);
+        }
+    }
+
+    let locations: Vec<String> = image.locations.iter().map(|location| location.display_name()).collect();
+    let caption = Label::new(Some(&locations.join("\n")));
+    caption.add_css_class("dim-label");
+    caption.add_css_class("caption");
+    caption.set_wrap(true);
+    caption.set_max_width_chars(20);
+    card.append(&caption);
+
+    let path = image.path.clone();
+    glib::spawn_future_local(async move {
+        match load_thumbnail(path, THUMBNAIL_SIZE).await {
+            Ok(texture) => picture.set_paintable(Some(&texture)),
+            Err(e) => eprintln!("Error while loading thumbnail: {}", e),
+        }
+    });
+
+    card
+}
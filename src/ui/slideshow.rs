@@ -0,0 +1,99 @@
+use glib::{ControlFlow, SourceId};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::models::ImageRecord;
+
+/// Identifies one run of a `Slideshow`, from `start` to `stop`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunToken(u64);
+
+/// Calls a callback at a fixed interval while running, skipping ticks while
+/// the pointer is over the image or the window is not focused.
+#[derive(Clone, Default)]
+pub struct Slideshow {
+    timer: Rc<RefCell<Option<SourceId>>>,
+    run: Rc<Cell<u64>>,
+    hovered: Rc<Cell<bool>>,
+    unfocused: Rc<Cell<bool>>,
+    pause_on_hover: Rc<Cell<bool>>,
+    position: Rc<Cell<usize>>,
+    playlist: Rc<RefCell<Vec<ImageRecord>>>,
+}
+
+impl Slideshow {
+    pub fn new() -> Self {
+        let slideshow = Self::default();
+        slideshow.pause_on_hover.set(true);
+        slideshow
+    }
+
+    /// Starts calling `on_tick` every `interval`, replacing any running timer
+    /// and its playlist.
+    pub fn start(&self, interval: Duration, on_tick: impl Fn() + 'static) -> RunToken {
+        self.stop();
+
+        let slideshow = self.clone();
+        let source_id = glib::timeout_add_local(interval, move || {
+            if !slideshow.is_paused() {
+                on_tick();
+            }
+            ControlFlow::Continue
+        });
+        *self.timer.borrow_mut() = Some(source_id);
+        RunToken(self.run.get())
+    }
+
+    pub fn stop(&self) {
+        if let Some(source_id) = self.timer.borrow_mut().take() {
+            source_id.remove();
+        }
+        self.run.set(self.run.get() + 1);
+        self.playlist.borrow_mut().clear();
+        self.position.set(0);
+    }
+
+    pub fn set_hovered(&self, hovered: bool) {
+        self.hovered.set(hovered);
+    }
+
+    pub fn set_focused(&self, focused: bool) {
+        self.unfocused.set(!focused);
+    }
+
+    /// In fullscreen the pointer is always over the image, so hovering must
+    /// not pause the slideshow there.
+    pub fn set_pause_on_hover(&self, pause_on_hover: bool) {
+        self.pause_on_hover.set(pause_on_hover);
+    }
+
+    /// Images to cycle through with `next_in_playlist`, loaded once when the
+    /// slideshow starts rather than at every tick. Ignored unless `run` is
+    /// still the current run, so a late load cannot replace a newer playlist.
+    pub fn set_playlist(&self, run: RunToken, images: Vec<ImageRecord>) {
+        if run.0 == self.run.get() && self.timer.borrow().is_some() {
+            *self.playlist.borrow_mut() = images;
+        }
+    }
+
+    pub fn next_in_playlist(&self) -> Option<ImageRecord> {
+        let playlist = self.playlist.borrow();
+        let position = self.next_position(playlist.len())?;
+        playlist.get(position).cloned()
+    }
+
+    /// Index of the next item when cycling through a list of `len` items.
+    fn next_position(&self, len: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        let position = self.position.get() % len;
+        self.position.set(position + 1);
+        Some(position)
+    }
+
+    fn is_paused(&self) -> bool {
+        self.unfocused.get() || (self.pause_on_hover.get() && self.hovered.get())
+    }
+}
@@ -0,0 +1,49 @@
+use gtk4::gdk;
+use gtk4::prelude::*;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// Decoded textures of recently shown images, so that going back and forth in
+/// history does not decode them again. Once their total size passes the
+/// memory budget, the least recently shown ones are dropped.
+#[derive(Clone, Default)]
+pub struct TextureCache {
+    entries: Rc<RefCell<VecDeque<(String, gdk::Texture)>>>,
+}
+
+impl TextureCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The texture of `url`, marking it as the most recently shown.
+    pub fn get(&self, url: &str) -> Option<gdk::Texture> {
+        let mut entries = self.entries.borrow_mut();
+        let position = entries.iter().position(|(cached_url, _)| cached_url == url)?;
+        let entry = entries.remove(position)?;
+        let texture = entry.1.clone();
+        entries.push_back(entry);
+        Some(texture)
+    }
+
+    /// Keeps `texture` for `url`, then drops the oldest textures until the
+    /// cache fits in `budget_bytes`. The new texture itself is always kept.
+    pub fn insert(&self, url: &str, texture: &gdk::Texture, budget_bytes: usize) {
+        let mut entries = self.entries.borrow_mut();
+        entries.retain(|(cached_url, _)| cached_url != url);
+        entries.push_back((url.to_string(), texture.clone()));
+
+        let mut total: usize = entries.iter().map(|(_, texture)| texture_bytes(texture)).sum();
+        while total > budget_bytes && entries.len() > 1 {
+            if let Some((_, evicted)) = entries.pop_front() {
+                total = total.saturating_sub(texture_bytes(&evicted));
+            }
+        }
+    }
+}
+
+/// Memory taken by a decoded texture, at four bytes per pixel.
+fn texture_bytes(texture: &gdk::Texture) -> usize {
+    texture.width() as usize * texture.height() as usize * 4
+}